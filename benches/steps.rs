@@ -0,0 +1,138 @@
+//! Compares the bidirectional search of `count-steps` and `steps` with the single ended
+//! breadth first searches they replaced, on a random graph.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use wikipedia_analysis::analyze::WikipediaAnalysis;
+use wikipedia_analysis::graph::{Direction, LinkGraph};
+use wikipedia_analysis::metadata::Metadata;
+use wikipedia_analysis::parse::{Article, ParserMode};
+
+const ARTICLE_COUNT: u32 = 50_000;
+const LINKS_PER_ARTICLE: u32 = 8;
+
+/// Builds a graph where each article links to random articles
+fn random_analysis() -> WikipediaAnalysis {
+    let mut rng = StdRng::seed_from_u64(20);
+    let article_map: HashMap<String, u32> = (0..ARTICLE_COUNT).map(|x| (format!("Article {}", x), x)).collect();
+    let mut articles: Vec<Article> = (0..ARTICLE_COUNT)
+        .map(|_| Article {
+            links: (0..LINKS_PER_ARTICLE).map(|_| rng.gen_range(0, ARTICLE_COUNT)).collect()
+        })
+        .collect();
+    let metadata = Metadata::new(ParserMode::OutgoingLinks);
+    let graph = LinkGraph::from_dataset("random", &metadata, &article_map, &mut articles, &HashMap::new()).unwrap();
+    return WikipediaAnalysis::new(graph);
+}
+
+/// The previous `get_number_of_steps_between_articles()`: a search from the destination only,
+/// without a visited set
+fn single_ended_steps(analysis: &WikipediaAnalysis, start_article: u32, destination_article: u32) -> Option<u32> {
+    let mut depth = 1;
+    let mut current_article_stack: Vec<u32> = Vec::new();
+    let mut next_article_stack: Vec<u32> = Vec::new();
+    current_article_stack.extend(analysis.incoming(destination_article));
+
+    loop {
+        for article_index in current_article_stack.drain(..) {
+            if article_index == start_article {
+                return Some(depth);
+            }
+            next_article_stack.extend(analysis.incoming(article_index));
+        }
+        current_article_stack.append(&mut next_article_stack);
+        if current_article_stack.len() == 0 {
+            break;
+        }
+        depth += 1;
+    }
+    return None;
+}
+
+/// The previous `get_path_between_articles()`: a search from the destination only, storing the path to
+/// every article in the frontier
+fn single_ended_path(analysis: &WikipediaAnalysis, start_article: u32, destination_article: u32) -> Option<Vec<u32>> {
+    let mut current_article_stack: Vec<Vec<u32>> = Vec::new();
+    let mut next_article_stack: Vec<Vec<u32>> = Vec::new();
+    let mut visited = vec![false; analysis.graph.len()];
+
+    for article in analysis.incoming(destination_article) {
+        current_article_stack.push(vec!(article));
+        visited[article as usize] = true;
+    }
+
+    loop {
+        for article_path in current_article_stack.drain(..) {
+            let current_article = article_path[article_path.len() - 1];
+            for next_article in analysis.incoming(current_article) {
+                if next_article == start_article {
+                    let mut path = vec![start_article];
+                    path.extend(article_path.iter().rev());
+                    path.push(destination_article);
+                    return Some(path);
+                }
+                if !visited[next_article as usize] {
+                    let mut next_path = article_path.clone();
+                    next_path.push(next_article);
+                    next_article_stack.push(next_path);
+                    visited[next_article as usize] = true;
+                }
+            }
+        }
+        current_article_stack.append(&mut next_article_stack);
+        if current_article_stack.len() == 0 {
+            break;
+        }
+    }
+    return None;
+}
+
+fn steps(c: &mut Criterion) {
+    let analysis = random_analysis();
+    let mut rng = StdRng::seed_from_u64(3);
+    let pairs: Vec<(u32, u32)> = (0..20)
+        .map(|_| (rng.gen_range(0, ARTICLE_COUNT), rng.gen_range(0, ARTICLE_COUNT)))
+        .filter(|(start, destination)| start != destination)
+        .collect();
+
+    // Both searches find shortest paths, so they must agree on the lengths
+    for (start, destination) in pairs.iter() {
+        let steps = analysis.get_number_of_steps_between_articles(*start, *destination, Direction::Incoming).unwrap();
+        assert_eq!(steps, single_ended_steps(&analysis, *start, *destination));
+        let path = analysis.get_path_between_articles(*start, *destination, Direction::Incoming).unwrap();
+        assert_eq!(path.map(|x| x.len()), single_ended_path(&analysis, *start, *destination).map(|x| x.len()));
+    }
+
+    let mut group = c.benchmark_group("count-steps");
+    group.sample_size(10);
+    group.bench_function("bidirectional", |b| b.iter(|| {
+        for (start, destination) in pairs.iter() {
+            analysis.get_number_of_steps_between_articles(*start, *destination, Direction::Incoming).unwrap();
+        }
+    }));
+    group.bench_function("single ended", |b| b.iter(|| {
+        for (start, destination) in pairs.iter() {
+            single_ended_steps(&analysis, *start, *destination);
+        }
+    }));
+    group.finish();
+
+    let mut group = c.benchmark_group("steps");
+    group.sample_size(10);
+    group.bench_function("bidirectional", |b| b.iter(|| {
+        for (start, destination) in pairs.iter() {
+            analysis.get_path_between_articles(*start, *destination, Direction::Incoming).unwrap();
+        }
+    }));
+    group.bench_function("single ended", |b| b.iter(|| {
+        for (start, destination) in pairs.iter() {
+            single_ended_path(&analysis, *start, *destination);
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, steps);
+criterion_main!(benches);
@@ -19,39 +19,37 @@ cargo build --release
 
 set -x # Enable echo
 
-# Generate datasets
-$EXECUTABLE parse --output $DATASETS_DIR/incoming-ignored.tsv --ignore-dir $IGNORE_DIR $WIKIPEDIA_DUMP_PATH
-$EXECUTABLE parse --output $DATASETS_DIR/incoming-no-ignore.tsv $WIKIPEDIA_DUMP_PATH
-$EXECUTABLE parse --output $DATASETS_DIR/outgoing-ignored.tsv -r --ignore-dir $IGNORE_DIR $WIKIPEDIA_DUMP_PATH
-$EXECUTABLE parse --output $DATASETS_DIR/outgoing-no-ignore.tsv -r $WIKIPEDIA_DUMP_PATH
+# Generate the dataset, it holds both incoming and outgoing links.
+# Ignored articles are left out at analysis time so one dataset is used for every result
+$EXECUTABLE parse --output $DATASETS_DIR/links.graph $WIKIPEDIA_DUMP_PATH
 
 # Generate link historgrams
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-ignored.tsv --output $RESULTS_DIR/outgoing-histogram-ignored.tsv link-histogram
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-ignored.tsv --output $RESULTS_DIR/incoming-histogram-ignored.tsv link-histogram
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-no-ignore.tsv --output $RESULTS_DIR/outgoing-histogram-no-ignore.tsv link-histogram
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-no-ignore.tsv --output $RESULTS_DIR/incoming-histogram-no-ignore.tsv link-histogram
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction outgoing --output $RESULTS_DIR/outgoing-histogram-ignored.tsv link-histogram
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction incoming --output $RESULTS_DIR/incoming-histogram-ignored.tsv link-histogram
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction outgoing --output $RESULTS_DIR/outgoing-histogram-no-ignore.tsv link-histogram
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction incoming --output $RESULTS_DIR/incoming-histogram-no-ignore.tsv link-histogram
 
 # Generate most linked
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-ignored.tsv --output $RESULTS_DIR/outgoing-most-linked-ignored.tsv most-linked
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-ignored.tsv --output $RESULTS_DIR/incoming-most-linked-ignored.tsv most-linked
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-no-ignore.tsv --output $RESULTS_DIR/outgoing-most-linked-no-ignore.tsv most-linked
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-no-ignore.tsv --output $RESULTS_DIR/incoming-most-linked-no-ignore.tsv most-linked
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction outgoing --output $RESULTS_DIR/outgoing-most-linked-ignored.tsv most-linked
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction incoming --output $RESULTS_DIR/incoming-most-linked-ignored.tsv most-linked
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction outgoing --output $RESULTS_DIR/outgoing-most-linked-no-ignore.tsv most-linked
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction incoming --output $RESULTS_DIR/incoming-most-linked-no-ignore.tsv most-linked
 
 # Generate step groups for top $STEP_GROUPS_LENGTH most linked articles
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-ignored.tsv --output $RESULTS_DIR/outgoing-step-groups-most-linked-ignored.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-ignored.tsv --output $RESULTS_DIR/incoming-step-groups-most-linked-ignored.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-no-ignore.tsv --output $RESULTS_DIR/outgoing-step-groups-most-linked-no-ignore.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-no-ignore.tsv --output $RESULTS_DIR/incoming-step-groups-most-linked-no-ignore.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction outgoing --output $RESULTS_DIR/outgoing-step-groups-most-linked-ignored.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction incoming --output $RESULTS_DIR/incoming-step-groups-most-linked-ignored.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction outgoing --output $RESULTS_DIR/outgoing-step-groups-most-linked-no-ignore.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction incoming --output $RESULTS_DIR/incoming-step-groups-most-linked-no-ignore.tsv step-groups --use-most-linked $STEP_GROUPS_LENGTH
 
 # Generate step groups for $STEP_GROUPS_LENGTH randomly selected articles
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-ignored.tsv --output $RESULTS_DIR/outgoing-step-groups-random-ignored.tsv step-groups --use-random $STEP_GROUPS_LENGTH
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction outgoing --output $RESULTS_DIR/outgoing-step-groups-random-ignored.tsv step-groups --use-random $STEP_GROUPS_LENGTH
 
 # Use the same random articles for the other datasets. Note we are choosing the random articles from an ignored corpus
 # as all articles in the ignored set should be in the non-ignored set.
 cat $RESULTS_DIR/outgoing-step-groups-random-ignored.tsv | cut -f1 | tail -n +2 > $RESULTS_DIR/random_article_names.txt
 
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-ignored.tsv --output $RESULTS_DIR/incoming-step-groups-random-ignored.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
-$EXECUTABLE analyze --input $DATASETS_DIR/outgoing-no-ignore.tsv --output $RESULTS_DIR/outgoing-step-groups-random-no-ignore.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
-$EXECUTABLE analyze --input $DATASETS_DIR/incoming-no-ignore.tsv --output $RESULTS_DIR/incoming-step-groups-random-no-ignore.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --ignore-dir $IGNORE_DIR --direction incoming --output $RESULTS_DIR/incoming-step-groups-random-ignored.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction outgoing --output $RESULTS_DIR/outgoing-step-groups-random-no-ignore.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
+$EXECUTABLE analyze --input $DATASETS_DIR/links.graph --direction incoming --output $RESULTS_DIR/incoming-step-groups-random-no-ignore.tsv step-groups --roots-file $RESULTS_DIR/random_article_names.txt
 
 set +x # Disable echo
\ No newline at end of file
@@ -1,21 +1,468 @@
 
-use std::collections::HashMap;
-
-use crate::parse::Article;
+use crate::graph::{Direction, LinkGraph};
+use crate::parse::ParserMode;
+use crate::ignore::IgnoreRules;
+use crate::error::{AnalysisError, AnalysisResult, ParseResult};
 use std::convert::TryInto;
+use std::collections::{BinaryHeap, HashSet};
 use std::mem;
+use std::cmp;
+use std::time::{Duration, Instant};
+
+/// Marks articles not yet reached by a `Search`
+const UNVISITED: u32 = u32::MAX;
+
+/// One side of the breadth first search of `WikipediaAnalysis::search_between_articles()`
+struct Search {
+    /// Direction of the links followed
+    direction: Direction,
+    /// Article each article was first reached from, indexed by article index.
+    /// The root is its own parent and articles not reached are `UNVISITED`.
+    parents: Vec<u32>,
+    /// Articles reached at the current depth
+    frontier: Vec<u32>,
+    /// Number of levels expanded
+    depth: u32
+}
+
+impl Search {
+
+    fn new(article_count: usize, root: u32, direction: Direction) -> Search {
+        let mut parents = vec![UNVISITED; article_count];
+        parents[root as usize] = root;
+        return Search {
+            direction,
+            parents,
+            frontier: vec![root],
+            depth: 0
+        };
+    }
+
+    fn is_visited(&self, article: u32) -> bool {
+        return self.parents[article as usize] != UNVISITED;
+    }
+
+    /// Expands the frontier by one level, not stepping to blocked articles other than the root of the other search.
+    ///
+    /// # Returns
+    /// The first article reached that the other search has visited, if any
+    ///
+    fn expand(&mut self, analysis: &WikipediaAnalysis, other: &Search, blocked: &Blocked) -> Option<u32> {
+        let mut next_frontier: Vec<u32> = Vec::new();
+        self.depth += 1;
+        for article in mem::take(&mut self.frontier) {
+            for next_article in analysis.links(article, self.direction) {
+                if self.is_visited(next_article) {
+                    continue;
+                }
+                if blocked.is_blocked(&analysis.graph, next_article) && other.parents[next_article as usize] != next_article {
+                    continue;
+                }
+                self.parents[next_article as usize] = article;
+                if other.is_visited(next_article) {
+                    return Some(next_article);
+                }
+                next_frontier.push(next_article);
+            }
+        }
+        self.frontier = next_frontier;
+        return None;
+    }
+
+    /// Gets the articles from a visited article back to the root, following the parents
+    fn path_to_root(&self, article: u32) -> Vec<u32> {
+        let mut path = vec![article];
+        let mut current = article;
+        while self.parents[current as usize] != current {
+            current = self.parents[current as usize];
+            path.push(current);
+        }
+        return path;
+    }
+}
+
+/// Where the two searches of `WikipediaAnalysis::search_between_articles()` met
+struct Meeting {
+    /// Article reached by both searches
+    article: u32,
+    from_start: Search,
+    from_destination: Search
+}
+
+impl Meeting {
+
+    /// Number of steps of the path found
+    fn steps(&self) -> u32 {
+        return self.from_start.depth + self.from_destination.depth;
+    }
+
+    /// Gets the path found, from the start article to the destination
+    fn path(&self) -> Vec<u32> {
+        // Follow the parents back to the start, then forward from the meeting point to the destination
+        let mut path = self.from_start.path_to_root(self.article);
+        path.reverse();
+        path.extend(self.from_destination.path_to_root(self.article).iter().skip(1));
+        return path;
+    }
+}
+
+/// Constraints on the paths found by `WikipediaAnalysis::get_constrained_path_between_articles()`.
+/// No constraints are set by default.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PathConstraints {
+    /// Articles the path may not go through
+    pub avoid: Vec<u32>,
+    /// Articles the path must go through, in order
+    pub via: Vec<u32>,
+    /// Maximum number of links (incoming and outgoing, in the dataset) of the articles the path goes through.
+    /// Hubs with many links otherwise appear on most paths.
+    pub max_degree: Option<usize>
+}
+
+/// Articles a search may not step to, built from `PathConstraints`
+struct Blocked {
+    /// Mask of the avoided articles, indexed by article index. Empty if no articles are avoided.
+    avoid: Vec<bool>,
+    max_degree: Option<usize>
+}
+
+impl Blocked {
+
+    /// Blocks no articles
+    fn none() -> Blocked {
+        return Blocked { avoid: Vec::new(), max_degree: None };
+    }
+
+    fn new(graph: &LinkGraph, constraints: &PathConstraints) -> Blocked {
+        let mut avoid: Vec<bool> = Vec::new();
+        if constraints.avoid.len() > 0 {
+            avoid = vec![false; graph.len()];
+            for article in constraints.avoid.iter() {
+                avoid[*article as usize] = true;
+            }
+        }
+        return Blocked { avoid, max_degree: constraints.max_degree };
+    }
+
+    fn is_blocked(&self, graph: &LinkGraph, article: u32) -> bool {
+        if self.avoid.len() != 0 && self.avoid[article as usize] {
+            return true;
+        }
+        return match self.max_degree {
+            Some(max_degree) => {
+                graph.links(article, Direction::Incoming).len() + graph.links(article, Direction::Outgoing).len() > max_degree
+            },
+            None => false
+        };
+    }
+}
+
+/// All the shortest paths between two articles, see `WikipediaAnalysis::get_all_paths_between_articles()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortestPaths {
+    /// Number of steps of each shortest path
+    pub steps: u32,
+    /// Number of shortest paths, `u64::MAX` if there are at least that many
+    pub count: u64,
+    /// Tuples of (article index, number of shortest paths through the article) for every article
+    /// part way along a shortest path, sorted by the number of paths
+    pub through: Vec<(u32, u64)>,
+    /// Some of the shortest paths, up to the maximum number requested. Each path is the indices of
+    /// the articles on it, from the start article to the destination.
+    pub paths: Vec<Vec<u32>>
+}
+
+/// Limits on the work done by `WikipediaAnalysis::get_k_shortest_paths_between_articles()`.
+/// No limit is set by default.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SearchBudget {
+    /// Maximum number of articles to expand (go through the links of), over all the searches
+    pub max_expansions: Option<u64>,
+    /// Maximum time to search for
+    pub time_limit: Option<Duration>
+}
+
+/// Number of articles expanded between checks of the time limit, as checking the time is comparatively slow
+const EXPANSIONS_PER_TIME_CHECK: u32 = 1024;
+
+/// Work done against a `SearchBudget`
+struct BudgetSpent {
+    budget: SearchBudget,
+    started: Instant,
+    expansions: u64,
+    /// Articles left to expand before the time limit is next checked
+    until_time_check: u32,
+    exhausted: bool
+}
+
+impl BudgetSpent {
+
+    fn new(budget: SearchBudget) -> BudgetSpent {
+        return BudgetSpent {
+            budget,
+            started: Instant::now(),
+            expansions: 0,
+            until_time_check: EXPANSIONS_PER_TIME_CHECK,
+            exhausted: false
+        };
+    }
+
+    /// Counts an article expanded.
+    ///
+    /// # Returns
+    /// False if the budget has run out
+    ///
+    fn expand(&mut self) -> bool {
+        self.expansions += 1;
+        if let Some(max_expansions) = self.budget.max_expansions {
+            if self.expansions > max_expansions {
+                self.exhausted = true;
+            }
+        }
+        if let Some(time_limit) = self.budget.time_limit {
+            self.until_time_check -= 1;
+            if self.until_time_check == 0 {
+                self.until_time_check = EXPANSIONS_PER_TIME_CHECK;
+                if self.started.elapsed() > time_limit {
+                    self.exhausted = true;
+                }
+            }
+        }
+        return !self.exhausted;
+    }
+}
+
+/// The shortest loopless paths between two articles, see `WikipediaAnalysis::get_k_shortest_paths_between_articles()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimplePaths {
+    /// The paths found in order of length, each the indices of the articles on it from the start article
+    /// to the destination. Paths of the same length are in order of the article indices on them.
+    pub paths: Vec<Vec<u32>>,
+    /// Whether the search ran out of budget before finding all the paths requested.
+    /// The paths found are still the shortest, but there may be more paths than were returned.
+    pub exhausted: bool
+}
+
+/// The connected components of the link graph, see `WikipediaAnalysis::get_strongly_connected_components()`
+/// and `WikipediaAnalysis::get_weakly_connected_components()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Components {
+    /// Component of each article, indexed by article index. Ignored articles are in no component, `u32::MAX`.
+    pub component_of: Vec<u32>,
+    /// Number of articles in each component, indexed by component
+    pub sizes: Vec<u32>
+}
+
+impl Components {
+
+    /// Gets the largest component, the lowest numbered if several are the largest.
+    /// None if there are no components.
+    pub fn largest(&self) -> Option<u32> {
+        let mut largest: Option<u32> = None;
+        for (component, size) in self.sizes.iter().enumerate() {
+            match largest {
+                Some(x) if self.sizes[x as usize] >= *size => (),
+                _ => largest = Some(component as u32)
+            }
+        }
+        return largest;
+    }
+
+    /// Gets the distribution of the component sizes.
+    ///
+    /// # Returns
+    /// Tuples of (component size, number of components of that size), sorted by size
+    ///
+    pub fn size_distribution(&self) -> Vec<(u32, usize)> {
+        let mut sizes = self.sizes.clone();
+        sizes.sort_unstable();
+        let mut distribution: Vec<(u32, usize)> = Vec::new();
+        for size in sizes {
+            match distribution.last_mut() {
+                Some((last_size, count)) if *last_size == size => *count += 1,
+                _ => distribution.push((size, 1))
+            }
+        }
+        return distribution;
+    }
+}
+
+/// Region of the bow-tie structure of a link graph an article is in, see `WikipediaAnalysis::get_bow_tie()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BowTieRegion {
+    /// The largest strongly connected component
+    GiantComponent,
+    /// Articles that can reach the giant component but cannot be reached from it
+    In,
+    /// Articles reachable from the giant component that cannot reach it
+    Out,
+    /// Articles reachable from the in region or that can reach the out region, but not both
+    Tendril,
+    /// Articles reachable from the in region that can reach the out region, not through the giant component
+    Tube,
+    /// Articles not connected to the giant component, the in region or the out region
+    Disconnected
+}
+
+impl BowTieRegion {
+
+    /// All the regions, in the order they are reported
+    pub const ALL: [BowTieRegion; 6] = [
+        BowTieRegion::GiantComponent,
+        BowTieRegion::In,
+        BowTieRegion::Out,
+        BowTieRegion::Tendril,
+        BowTieRegion::Tube,
+        BowTieRegion::Disconnected
+    ];
+
+    /// Name of the region used in reports
+    pub fn name(&self) -> &'static str {
+        return match self {
+            BowTieRegion::GiantComponent => "giant-scc",
+            BowTieRegion::In => "in",
+            BowTieRegion::Out => "out",
+            BowTieRegion::Tendril => "tendril",
+            BowTieRegion::Tube => "tube",
+            BowTieRegion::Disconnected => "disconnected"
+        };
+    }
+}
 
 /// Implements functions for analysing the parsed wikipedia data.
+///
+/// Articles can be ignored, see `ignore_articles()`. Ignored articles are left out of every analysis
+/// as if they were not in the dataset: they are not counted, not linked to or from and not stepped through.
 pub struct WikipediaAnalysis {
-    /// A HashMap of article name -> article index
-    pub article_map: HashMap<String, u32>,
-    /// An adjacency list representation of the incoming links to each article.
-    pub articles: Vec<Article>
+    /// The graph of the incoming and outgoing links of each article, with article titles and redirects.
+    pub graph: LinkGraph,
+    /// Mask of the ignored articles, indexed by article index. Empty if no articles are ignored.
+    ignored: Vec<bool>,
+    /// Number of articles ignored
+    ignored_count: usize
 }
 
 
 impl WikipediaAnalysis {
 
+    /// Creates an analysis of a graph with no articles ignored.
+    pub fn new(graph: LinkGraph) -> WikipediaAnalysis {
+        return WikipediaAnalysis {
+            graph,
+            ignored: Vec::new(),
+            ignored_count: 0
+        };
+    }
+
+    /// Opens a dataset for analysis with no articles ignored, see `LinkGraph::open()`.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the dataset, binary or TSV
+    /// * `mode` - Direction of the links in datasets that do not record it
+    ///
+    pub fn open(path: &str, mode: ParserMode) -> ParseResult<WikipediaAnalysis> {
+        return Ok(WikipediaAnalysis::new(LinkGraph::open(path, mode)?));
+    }
+
+    /// Ignores the articles matching ignore rules, in addition to any already ignored.
+    ///
+    /// Every article title is checked against the rules, as when ignoring articles while parsing,
+    /// so the match counts of the rules are updated.
+    ///
+    /// # Arguments
+    /// * `rules` - Rules for the titles of the articles to ignore
+    ///
+    /// # Returns
+    /// The number of articles newly ignored.
+    ///
+    pub fn ignore_articles(&mut self, rules: &IgnoreRules) -> usize {
+        if self.ignored.len() == 0 {
+            self.ignored = vec![false; self.graph.len()];
+        }
+        let mut count = 0;
+        for index in 0..self.graph.len() {
+            if rules.matches(self.graph.title(index as u32)) && !self.ignored[index] {
+                self.ignored[index] = true;
+                count += 1;
+            }
+        }
+        self.ignored_count += count;
+        return count;
+    }
+
+    /// Checks if an article is ignored
+    pub fn is_ignored(&self, article: u32) -> bool {
+        return self.ignored.len() != 0 && self.ignored[article as usize];
+    }
+
+    /// Number of articles analysed, ie the articles that are not ignored
+    pub fn article_count(&self) -> usize {
+        return self.graph.len() - self.ignored_count;
+    }
+
+    /// Gets the indices of the articles that are not ignored
+    pub fn articles(&self) -> impl Iterator<Item = u32> + '_ {
+        return (0..self.graph.len() as u32).filter(move |x| !self.is_ignored(*x));
+    }
+
+    /// Checks an article index is in the graph and not ignored
+    pub fn check_article(&self, article: u32) -> AnalysisResult<()> {
+        self.graph.check_article(article)?;
+        if self.is_ignored(article) {
+            return Err(AnalysisError::ArticleIgnored(article));
+        }
+        return Ok(());
+    }
+
+    /// Finds an article by name, following redirects, see `LinkGraph::resolve()`.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the article or of a redirect to the article
+    ///
+    /// # Returns
+    /// A tuple of (article index, whether the name was a redirect), or None if no article
+    /// or redirect with the name exists or the article is ignored.
+    ///
+    pub fn find_article(&self, name: &str) -> Option<(u32, bool)> {
+        return match self.graph.resolve(name) {
+            Some((index, _)) if self.is_ignored(index) => None,
+            result => result
+        };
+    }
+
+    /// Gets the index of an article by name, following redirects, see `find_article()`.
+    pub fn index_of(&self, name: &str) -> AnalysisResult<u32> {
+        return match self.find_article(name) {
+            Some((index, _)) => Ok(index),
+            None => Err(AnalysisError::ArticleNotFound(name.to_string()))
+        };
+    }
+
+    /// Gets the links to or from an article, leaving out ignored articles.
+    ///
+    /// # Arguments
+    /// * `article` - Index of the article
+    /// * `direction` - Whether to get the articles linking to the article (incoming)
+    ///   or the articles it links to (outgoing)
+    ///
+    pub fn links(&self, article: u32, direction: Direction) -> impl Iterator<Item = u32> + '_ {
+        return self.graph
+            .links(article, direction)
+            .iter()
+            .copied()
+            .filter(move |x| !self.is_ignored(*x));
+    }
+
+    /// Gets the articles linking to an article, leaving out ignored articles
+    pub fn incoming(&self, article: u32) -> impl Iterator<Item = u32> + '_ {
+        return self.links(article, Direction::Incoming);
+    }
+
+    /// Gets the articles an article links to, leaving out ignored articles
+    pub fn outgoing(&self, article: u32) -> impl Iterator<Item = u32> + '_ {
+        return self.links(article, Direction::Outgoing);
+    }
+
     /// Initialises a vector with the given default up to the index.
     fn vec_initialise_up_to_index<T: Clone>(vec: &mut Vec<T>, index: usize, default: T) {
         while index >= vec.len() {
@@ -27,159 +474,546 @@ impl WikipediaAnalysis {
     ///
     /// # Arguments
     /// * `count` - Number of items to return
+    /// * `direction` - Whether to count the links to (incoming) or from (outgoing) each page
     ///
     /// # Returns
     /// A sorted vector of tuples of (article index, link count).
     /// The vector is of length `count` unless `count` exceeds the number of articles.
     ///
-    pub fn get_most_links(&self, count: u32) -> Vec<(u32, u32)> {
+    pub fn get_most_links(&self, count: u32, direction: Direction) -> Vec<(u32, u32)> {
         let mut link_counts_map = Vec::new();
-        for (_article_name, index) in self.article_map.iter() {
+        for index in self.articles() {
             link_counts_map.push(
-                (*index, self.articles[*index as usize].links.len().try_into().unwrap()));
+                (index, self.links(index, direction).count().try_into().unwrap()));
         }
-        link_counts_map.sort_unstable_by(|a:&(u32, u32), b: &(u32, u32)| b.1.cmp(&a.1));
-        return link_counts_map[0..count as usize].to_vec();
+        link_counts_map.sort_unstable_by_key(|a: &(u32, u32)| cmp::Reverse(a.1));
+        link_counts_map.truncate(count as usize);
+        return link_counts_map;
     }
 
     /// Gets a histogram of the number of links per page.
     ///
+    /// # Arguments
+    /// * `direction` - Whether to count the links to (incoming) or from (outgoing) each page
+    ///
     /// # Returns
     /// Returns a vector indexed by the number of links.
     /// Values are the number of pages with that number of links.
-    pub fn get_links_histogram(&self) -> Vec<u32> {
+    pub fn get_links_histogram(&self, direction: Direction) -> Vec<u32> {
         let mut link_counts: Vec<u32> = Vec::new();
-        for article in self.articles.iter() {
+        for index in self.articles() {
+            let link_count = self.links(index, direction).count();
             WikipediaAnalysis::vec_initialise_up_to_index(
                 &mut link_counts,
-                article.links.len(),
+                link_count,
                 0
             );
-            link_counts[article.links.len()] += 1;
+            link_counts[link_count] += 1;
         }
         return link_counts;
     }
 
+    /// Searches for a shortest path between two articles with a breadth first search from both ends.
+    ///
+    /// The search expands the smaller of the two frontiers a whole level at a time: from the start article
+    /// along links in the opposite direction to `direction`, and from the destination along `direction`.
+    /// The first article reached by both searches is on a shortest path, as any article reached by the
+    /// other search at a lower depth would have been met when that search expanded it. Each search records
+    /// the article it reached every article from, so the path is rebuilt without storing a path per article.
+    ///
+    /// # Arguments
+    /// * `start_article` - The article to start stepping from
+    /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links searched, from the destination article
+    /// * `blocked` - Articles the path may not go through, other than the start and destination
+    ///
+    /// # Returns
+    /// The meeting point of the two searches, or None if there is no path.
+    ///
+    fn search_between_articles(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        blocked: &Blocked) -> AnalysisResult<Option<Meeting>> {
+
+        self.check_article(start_article)?;
+        self.check_article(destination_article)?;
+
+        let mut from_start = Search::new(self.graph.len(), start_article, direction.reverse());
+        let mut from_destination = Search::new(self.graph.len(), destination_article, direction);
+        if start_article == destination_article {
+            return Ok(Some(Meeting { article: start_article, from_start, from_destination }));
+        }
+
+        while from_start.frontier.len() > 0 && from_destination.frontier.len() > 0 {
+            let (search, other) = match from_start.frontier.len() <= from_destination.frontier.len() {
+                true => (&mut from_start, &from_destination),
+                false => (&mut from_destination, &from_start)
+            };
+            if let Some(article) = search.expand(self, other, blocked) {
+                return Ok(Some(Meeting { article, from_start, from_destination }));
+            }
+        }
+        return Ok(None);
+    }
+
     /// Gets the number of steps between two articles.
     /// Steps refers to points on the path of links between the articles.
     ///
-    /// Note: Argument names are intended for incoming link representation. If you are using an
-    /// input file that was generated as outgoing links then the arguments are reversed,
+    /// Note: Argument names are intended for searching incoming links, which finds a path following
+    /// links from the start article to the destination. Searching outgoing links reverses the arguments.
     ///
     /// # Arguments
     /// * `start_article` - The article to start stepping from
     /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links searched, from the destination article
     ///
     /// # Remarks
     /// This does not return the path itself, use `get_path_between_articles()` to get the path.
+    /// Both search from the two articles at once, see `search_between_articles()`.
     ///
     /// # Returns
-    /// The number of steps between the two articles.
+    /// The number of steps between the two articles, 0 if they are the same article.
     /// If no path is found None is returned.
     ///
+    /// # Errors
+    /// Returns an error if either article is out of range or ignored
+    ///
     pub fn get_number_of_steps_between_articles(
         &self,
         start_article: u32,
-        destination_article: u32) -> Option<u32> {
-
-        // Perform a breadth-first-search for destination article from start article
-        // BFS guarantees shortest path
-        let mut depth = 1;
-        let mut current_article_stack: Vec<u32> = Vec::new();
-        let mut next_article_stack: Vec<u32> = Vec::new();
-        let mut starting_links = self.articles[destination_article as usize].links.clone();
-        current_article_stack.append(&mut starting_links);
-
-        loop {
-            for article_index in current_article_stack.drain(..) {
-                if article_index == start_article {
-                    return Some(depth);
-                }
-                next_article_stack.extend(self.articles[article_index as usize].links.iter());
+        destination_article: u32,
+        direction: Direction) -> AnalysisResult<Option<u32>> {
 
-            }
-            current_article_stack.extend(next_article_stack.iter());
-            if current_article_stack.len() == 0 {
-                break;
-            }
-            depth += 1;
-        }
-        return None;
+        let meeting = self.search_between_articles(start_article, destination_article, direction, &Blocked::none())?;
+        return Ok(meeting.map(|x| x.steps()));
     }
 
     /// Gets the path between two articles.
     /// Steps refers to points on the path of links between the articles.
     ///
-    /// Note: Argument names are intended for incoming link representation. If you are using an
-    /// input file that was generated as outgoing links then the arguments are reversed,
+    /// Note: Argument names are intended for searching incoming links, which finds a path following
+    /// links from the start article to the destination. Searching outgoing links reverses the arguments.
     ///
     /// # Arguments
     /// * `start_article` - The article to start stepping from
     /// * `destination_article` - The article to reach
-    ///
-    /// # Remarks
-    /// If you only need to establish if a path exists and/or how long it is, use
-    /// `get_number_of_steps_between_articles()` instead as it is faster and lower in memory usage.
+    /// * `direction` - Direction of the links searched, from the destination article
     ///
     /// # Returns
-    /// A vec with the name of article steps between the two articles.
+    /// A vec of the indices of the articles on the path, from the start article to the destination.
     /// If no path is found None is returned.
     ///
+    /// # Errors
+    /// Returns an error if either article is out of range or ignored
+    ///
     pub fn get_path_between_articles(
         &self,
         start_article: u32,
-        destination_article: u32) -> Option<Vec<u32>> {
+        destination_article: u32,
+        direction: Direction) -> AnalysisResult<Option<Vec<u32>>> {
 
-        // Perform a breadth-first-search for destination article from start article
-        // BFS guarantees shortest path
-        let mut current_article_stack: Vec<Vec<u32>> = Vec::new();
-        let mut next_article_stack: Vec<Vec<u32>> = Vec::new();
+        let meeting = self.search_between_articles(start_article, destination_article, direction, &Blocked::none())?;
+        return Ok(meeting.map(|x| x.path()));
+    }
 
-        // See `get_step_count_groups()` for how this works
-        const BITS_PER_BYTE: usize = 8;
-        const BITMASK: usize = BITS_PER_BYTE * mem::size_of::<usize>() - 1;
-        const LOG2_BITS_PER_USIZE: usize = BITMASK.count_ones() as usize;
-        let mut visited: Vec<usize> = vec![0; (self.articles.len() >> LOG2_BITS_PER_USIZE as usize) + 1];
+    /// Searches for a path between two articles with constraints, one search per leg between the
+    /// start, the via articles and the destination.
+    ///
+    /// # Returns
+    /// The meeting point of the search of each leg, or None if a leg has no path.
+    ///
+    fn search_with_constraints(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        constraints: &PathConstraints) -> AnalysisResult<Option<Vec<Meeting>>> {
 
-        for article in self.articles[destination_article as usize].links.iter() {
-            current_article_stack.push(vec!(*article));
-            visited[*article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (*article as usize & BITMASK);
+        for article in constraints.avoid.iter().chain(constraints.via.iter()) {
+            self.check_article(*article)?;
         }
+        let blocked = Blocked::new(&self.graph, constraints);
 
-        loop {
-            for article_path in current_article_stack.drain(..) {
-                let current_article = article_path[article_path.len() - 1];
+        let mut waypoints: Vec<u32> = vec![start_article];
+        waypoints.extend(constraints.via.iter());
+        waypoints.push(destination_article);
 
-                for next_article in self.articles[current_article as usize].links.iter() {
+        let mut meetings: Vec<Meeting> = Vec::new();
+        for leg in waypoints.windows(2) {
+            match self.search_between_articles(leg[0], leg[1], direction, &blocked)? {
+                Some(meeting) => meetings.push(meeting),
+                None => return Ok(None)
+            }
+        }
+        return Ok(Some(meetings));
+    }
 
-                    // First time this is seen is guaranteed to be
-                    // the shortest (or equal-shortest) route
-                    if *next_article == start_article {
-                        let mut path = Vec::with_capacity(article_path.len() + 1);
+    /// Gets the number of steps between two articles on a path meeting constraints,
+    /// see `get_constrained_path_between_articles()`.
+    ///
+    /// # Returns
+    /// The number of steps between the two articles, or None if no path meets the constraints.
+    ///
+    /// # Errors
+    /// Returns an error if any of the articles given is out of range or ignored
+    ///
+    pub fn get_constrained_number_of_steps_between_articles(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        constraints: &PathConstraints) -> AnalysisResult<Option<u32>> {
 
-                        // Generate output vec in correct order
-                        path.push(start_article);
-                        for index in article_path.iter().rev() {
-                            path.push(*index);
-                        }
-                        path.push(destination_article);
-                        return Some(path);
+        let meetings = self.search_with_constraints(start_article, destination_article, direction, constraints)?;
+        return Ok(meetings.map(|x| x.iter().map(|meeting| meeting.steps()).sum()));
+    }
+
+    /// Gets the path between two articles that avoids some articles, goes through others in order
+    /// and leaves out articles with many links.
+    ///
+    /// Note: Argument names are intended for searching incoming links, as for `get_path_between_articles()`.
+    ///
+    /// # Arguments
+    /// * `start_article` - The article to start stepping from
+    /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links searched, from the destination article
+    /// * `constraints` - The articles to avoid, to go through and the maximum number of links
+    ///
+    /// # Remarks
+    /// The constraints apply to the articles part way along the path, the start, destination and via
+    /// articles are never left out. The shortest path of each leg between the via articles is found
+    /// separately, so the path is the shortest through the via articles but may go through an article twice.
+    ///
+    /// # Returns
+    /// A vec of the indices of the articles on the path, from the start article to the destination.
+    /// If no path meets the constraints None is returned.
+    ///
+    /// # Errors
+    /// Returns an error if any of the articles given is out of range or ignored
+    ///
+    pub fn get_constrained_path_between_articles(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        constraints: &PathConstraints) -> AnalysisResult<Option<Vec<u32>>> {
+
+        let meetings = match self.search_with_constraints(start_article, destination_article, direction, constraints)? {
+            Some(meetings) => meetings,
+            None => return Ok(None)
+        };
+        let mut path: Vec<u32> = vec![start_article];
+        for meeting in meetings.iter() {
+            path.extend(meeting.path().iter().skip(1));
+        }
+        return Ok(Some(path));
+    }
+
+    /// Counts all the shortest paths between two articles, and the shortest paths through each article.
+    ///
+    /// A breadth first search from the start article counts the shortest paths to each article it reaches,
+    /// stopping at the depth of the destination. Going back from the destination one depth at a time then
+    /// counts the shortest paths from each article to the destination. The paths through an article are the
+    /// product of the two counts, so the paths themselves are only built for the ones listed.
+    ///
+    /// Note: Argument names are intended for searching incoming links, as for `get_path_between_articles()`.
+    ///
+    /// # Arguments
+    /// * `start_article` - The article to start stepping from
+    /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links searched, from the destination article
+    /// * `max_paths` - Maximum number of paths to list
+    ///
+    /// # Returns
+    /// The shortest paths, or None if there is no path. Counts saturate at `u64::MAX`.
+    ///
+    /// # Errors
+    /// Returns an error if either article is out of range or ignored
+    ///
+    pub fn get_all_paths_between_articles(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        max_paths: usize) -> AnalysisResult<Option<ShortestPaths>> {
+
+        self.check_article(start_article)?;
+        self.check_article(destination_article)?;
+        if start_article == destination_article {
+            return Ok(Some(ShortestPaths {
+                steps: 0,
+                count: 1,
+                through: Vec::new(),
+                paths: vec![vec![start_article]].into_iter().take(max_paths).collect()
+            }));
+        }
+
+        // Depth of each article from the start and the number of shortest paths to it
+        let mut depths: Vec<u32> = vec![UNVISITED; self.graph.len()];
+        let mut paths_from_start: Vec<u64> = vec![0; self.graph.len()];
+        depths[start_article as usize] = 0;
+        paths_from_start[start_article as usize] = 1;
+
+        let mut levels: Vec<Vec<u32>> = vec![vec![start_article]];
+        while depths[destination_article as usize] == UNVISITED {
+            let depth = levels.len() as u32;
+            let mut next_level: Vec<u32> = Vec::new();
+            for article in levels[levels.len() - 1].iter() {
+                for next_article in self.links(*article, direction.reverse()) {
+                    if depths[next_article as usize] == UNVISITED {
+                        depths[next_article as usize] = depth;
+                        next_level.push(next_article);
+                    }
+                    if depths[next_article as usize] == depth {
+                        paths_from_start[next_article as usize] = paths_from_start[next_article as usize]
+                            .saturating_add(paths_from_start[*article as usize]);
                     }
+                }
+            }
+            if next_level.len() == 0 {
+                return Ok(None);
+            }
+            levels.push(next_level);
+        }
+        let steps = depths[destination_article as usize];
 
-                    if (visited[*next_article as usize >> LOG2_BITS_PER_USIZE] & 1 << (*next_article as usize & BITMASK)) == 0 {
-                        let mut next_path = article_path.clone();
-                        next_path.push(*next_article);
-                        next_article_stack.push(next_path);
-                        visited[*next_article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (*next_article as usize & BITMASK);
+        // Number of shortest paths from each article to the destination, 0 for articles not on a shortest path
+        let mut paths_to_destination: Vec<u64> = vec![0; self.graph.len()];
+        paths_to_destination[destination_article as usize] = 1;
+        for depth in (1..=steps).rev() {
+            for article in levels[depth as usize].iter() {
+                let count = paths_to_destination[*article as usize];
+                if count == 0 {
+                    continue;
+                }
+                for previous_article in self.links(*article, direction) {
+                    if depths[previous_article as usize] == depth - 1 {
+                        paths_to_destination[previous_article as usize] = paths_to_destination[previous_article as usize]
+                            .saturating_add(count);
                     }
                 }
             }
-            current_article_stack.extend(next_article_stack.drain(..));
-            if current_article_stack.len() == 0 {
-                break;
+        }
+
+        let mut through: Vec<(u32, u64)> = levels[1..steps as usize]
+            .iter()
+            .flatten()
+            .filter(|x| paths_to_destination[**x as usize] > 0)
+            .map(|x| (*x, paths_from_start[*x as usize].saturating_mul(paths_to_destination[*x as usize])))
+            .collect();
+        through.sort_unstable_by_key(|x| (cmp::Reverse(x.1), x.0));
+
+        // List paths with a depth first search over the articles on shortest paths
+        let mut paths: Vec<Vec<u32>> = Vec::new();
+        let mut path: Vec<u32> = vec![start_article];
+        let mut next_articles: Vec<Vec<u32>> = Vec::new();
+        // Next articles along shortest paths, in reverse so they are popped in index order
+        let next_on_shortest_path = |article: u32, depth: u32| -> Vec<u32> {
+            let mut next: Vec<u32> = self
+                .links(article, direction.reverse())
+                .filter(|x| depths[*x as usize] == depth + 1 && paths_to_destination[*x as usize] > 0)
+                .collect();
+            next.reverse();
+            return next;
+        };
+        next_articles.push(next_on_shortest_path(start_article, 0));
+        while paths.len() < max_paths && next_articles.len() > 0 {
+            let depth = next_articles.len() as u32;
+            match next_articles.last_mut().and_then(|x| x.pop()) {
+                Some(article) => {
+                    path.push(article);
+                    if article == destination_article {
+                        paths.push(path.clone());
+                        path.pop();
+                    }
+                    else {
+                        next_articles.push(next_on_shortest_path(article, depth));
+                    }
+                },
+                None => {
+                    next_articles.pop();
+                    path.pop();
+                }
             }
         }
-        return None;
+
+        return Ok(Some(ShortestPaths {
+            steps,
+            count: paths_from_start[destination_article as usize],
+            through,
+            paths
+        }));
+    }
+
+    /// Finds a shortest path with a breadth first search from one article, used for the spur paths of
+    /// `get_k_shortest_paths_between_articles()`.
+    ///
+    /// # Arguments
+    /// * `start_article` - The article to start stepping from
+    /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links followed from the start article
+    /// * `avoid` - Articles the path may not go through
+    /// * `avoid_first` - Articles the path may not step to from the start article
+    /// * `parents` - Buffer of the article each article was reached from, all `UNVISITED`. It is
+    ///   left all `UNVISITED` again so it can be reused without reallocating it for each search.
+    /// * `spent` - Budget of the search
+    ///
+    /// # Returns
+    /// The indices of the articles on the path, from the start article to the destination.
+    /// If no path is found or the budget runs out None is returned.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    fn search_avoiding(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        avoid: &[u32],
+        avoid_first: &[u32],
+        parents: &mut [u32],
+        spent: &mut BudgetSpent) -> Option<Vec<u32>> {
+
+        // Articles with parents set, to reset once done
+        let mut visited: Vec<u32> = Vec::new();
+        for article in avoid.iter().chain(std::iter::once(&start_article)) {
+            parents[*article as usize] = *article;
+            visited.push(*article);
+        }
+
+        let mut found = start_article == destination_article;
+        let mut frontier: Vec<u32> = vec![start_article];
+        while !found && frontier.len() > 0 && !spent.exhausted {
+            let mut next_frontier: Vec<u32> = Vec::new();
+            for article in frontier.iter() {
+                if !spent.expand() {
+                    break;
+                }
+                for next_article in self.links(*article, direction) {
+                    if parents[next_article as usize] != UNVISITED
+                        || (*article == start_article && avoid_first.contains(&next_article)) {
+                        continue;
+                    }
+                    parents[next_article as usize] = *article;
+                    visited.push(next_article);
+                    if next_article == destination_article {
+                        found = true;
+                        break;
+                    }
+                    next_frontier.push(next_article);
+                }
+                if found {
+                    break;
+                }
+            }
+            frontier = next_frontier;
+        }
+
+        let mut path: Option<Vec<u32>> = None;
+        if found {
+            let mut reversed = vec![destination_article];
+            let mut current = destination_article;
+            while current != start_article {
+                current = parents[current as usize];
+                reversed.push(current);
+            }
+            reversed.reverse();
+            path = Some(reversed);
+        }
+        for article in visited {
+            parents[article as usize] = UNVISITED;
+        }
+        return path;
+    }
+
+    /// Gets the shortest loopless paths between two articles, ranked by length, using Yen's algorithm.
+    ///
+    /// Each path after the first is found by branching off one of the paths already found: for each
+    /// article along it (the spur), the shortest path from the spur that does not go back through the
+    /// articles before it or repeat the step taken by any path found with the same beginning.
+    /// The shortest of these candidates is the next path.
+    ///
+    /// Note: Argument names are intended for searching incoming links, as for `get_path_between_articles()`.
+    ///
+    /// # Arguments
+    /// * `start_article` - The article to start stepping from
+    /// * `destination_article` - The article to reach
+    /// * `direction` - Direction of the links searched, from the destination article
+    /// * `k` - Number of paths to find
+    /// * `budget` - Limits on the searches for the paths after the first
+    ///
+    /// # Remarks
+    /// Finding each path searches from every article of the previous path, so this is much slower than
+    /// `get_path_between_articles()` on a large graph. The budget stops the search early, keeping the paths
+    /// already found.
+    ///
+    /// # Returns
+    /// Up to `k` paths, empty if there is no path.
+    ///
+    /// # Errors
+    /// Returns an error if either article is out of range or ignored
+    ///
+    pub fn get_k_shortest_paths_between_articles(
+        &self,
+        start_article: u32,
+        destination_article: u32,
+        direction: Direction,
+        k: usize,
+        budget: SearchBudget) -> AnalysisResult<SimplePaths> {
+
+        let mut found: Vec<Vec<u32>> = Vec::new();
+        if k > 0 {
+            if let Some(path) = self.get_path_between_articles(start_article, destination_article, direction)? {
+                found.push(path);
+            }
+        }
+
+        let mut spent = BudgetSpent::new(budget);
+        let mut parents: Vec<u32> = Vec::new();
+        // Candidates ordered by length then by the articles on them, and every path seen so none are repeated
+        let mut candidates: BinaryHeap<cmp::Reverse<(usize, Vec<u32>)>> = BinaryHeap::new();
+        let mut seen: HashSet<Vec<u32>> = found.iter().cloned().collect();
+
+        while found.len() > 0 && found.len() < k {
+            if parents.len() == 0 {
+                parents = vec![UNVISITED; self.graph.len()];
+            }
+            let previous = found[found.len() - 1].clone();
+            for spur_index in 0..previous.len() - 1 {
+                let root = &previous[..=spur_index];
+                let avoid_first: Vec<u32> = found
+                    .iter()
+                    .filter(|x| x.len() > spur_index + 1 && &x[..=spur_index] == root)
+                    .map(|x| x[spur_index + 1])
+                    .collect();
+
+                let spur_path = self.search_avoiding(
+                    previous[spur_index],
+                    destination_article,
+                    direction.reverse(),
+                    &root[..spur_index],
+                    &avoid_first,
+                    &mut parents,
+                    &mut spent);
+                if spent.exhausted {
+                    return Ok(SimplePaths { paths: found, exhausted: true });
+                }
+                if let Some(spur_path) = spur_path {
+                    let mut path = root[..spur_index].to_vec();
+                    path.extend(spur_path);
+                    if seen.insert(path.clone()) {
+                        candidates.push(cmp::Reverse((path.len(), path)));
+                    }
+                }
+            }
+
+            match candidates.pop() {
+                Some(cmp::Reverse((_, path))) => found.push(path),
+                None => break
+            }
+        }
+        return Ok(SimplePaths { paths: found, exhausted: false });
     }
 
     /// Gets a list of articles at each step from the starting article.
@@ -189,8 +1023,10 @@ impl WikipediaAnalysis {
     /// b would be in step group 1 and c would be in step group 2.
     ///
     /// # Arguments
-    /// * `root_article` - The root of the incoming link tree.
-    /// * `max_depth` - The maximum depth of the incoming link tree to evaluate.
+    /// * `root_article` - The root of the link tree.
+    /// * `max_depth` - The maximum depth of the link tree to evaluate.
+    /// * `direction` - Whether the tree is of the articles linking to the root (incoming),
+    ///   or of the articles linked from the root (outgoing)
     ///
     /// # Remarks
     /// If you only need to establish if a path exists and/or how long it is, use
@@ -198,10 +1034,10 @@ impl WikipediaAnalysis {
     ///
     /// # Returns
     /// A vec indexed by the step count group, containing items which are
-    /// a vec of the indices of incoming links th that group.
+    /// a vec of the indices of the linked articles in that group.
     ///
     /// Eg in the example:
-    /// ```
+    /// ```text
     ///     a
     ///    /  \
     ///   b    c
@@ -211,19 +1047,24 @@ impl WikipediaAnalysis {
     /// Where the indices of a, b, c, d are 0, 1, 2, 3
     /// the result would be [[0], [1, 2], [3]].
     ///
+    /// # Errors
+    /// Returns an error if the root article is out of range or ignored
+    ///
     pub fn get_step_count_groups(
         &self,
         root_article: u32,
-        max_depth: Option<u32>) -> Vec<Vec<u32>> {
+        max_depth: Option<u32>,
+        direction: Direction) -> AnalysisResult<Vec<Vec<u32>>> {
 
+        self.check_article(root_article)?;
         let root_article = root_article as usize;
 
         let mut depth = match max_depth {
             Some(depth) => depth,
-            None => self.articles.len().try_into().unwrap()
+            None => self.graph.len().try_into().unwrap()
         };
         let mut groups: Vec<Vec<u32>> = Vec::new();
-        groups.push( self.articles[root_article].links.clone());
+        groups.push( self.links(root_article as u32, direction).collect());
 
         // Array to check if a node has been visited
         // As this is a bitfield we are going to pack the bits as tightly as possible
@@ -234,26 +1075,26 @@ impl WikipediaAnalysis {
         const BITMASK: usize = BITS_PER_BYTE * mem::size_of::<usize>() - 1;
         const LOG2_BITS_PER_USIZE: usize = BITMASK.count_ones() as usize;
 
-        let mut visited: Vec<usize> = vec![0; (self.articles.len() >> LOG2_BITS_PER_USIZE as usize) + 1];
+        let mut visited: Vec<usize> = vec![0; (self.graph.len() >> LOG2_BITS_PER_USIZE) + 1];
 
         // Initialise visited elements
         // Visited is set before expanding a node to avoid having
         // multiple of the same nodes in the to visit group.
         visited[root_article >> LOG2_BITS_PER_USIZE] |= 1 << (root_article & BITMASK);
-        for next_article in self.articles[root_article].links.iter() {
-            visited[*next_article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (*next_article as usize & BITMASK);
+        for next_article in self.links(root_article as u32, direction) {
+            visited[next_article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (next_article as usize & BITMASK);
         }
 
         while depth > 1 {
             let current_article_stack = &groups[groups.len() - 1];
             let mut next_article_stack: Vec<u32> = Vec::new();
             for current_article in current_article_stack.iter() {
-                for next_article in self.articles[*current_article as usize].links.iter() {
+                for next_article in self.links(*current_article, direction) {
                     // Check if article has been visited
-                    if (visited[*next_article as usize >> LOG2_BITS_PER_USIZE] & 1 << (*next_article as usize & BITMASK)) == 0 {
-                        next_article_stack.push(*next_article);
+                    if (visited[next_article as usize >> LOG2_BITS_PER_USIZE] & 1 << (next_article as usize & BITMASK)) == 0 {
+                        next_article_stack.push(next_article);
                         // Mark article visited
-                        visited[*next_article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (*next_article as usize & BITMASK);
+                        visited[next_article as usize >> LOG2_BITS_PER_USIZE] |= 1 << (next_article as usize & BITMASK);
                     }
                 }
             }
@@ -265,6 +1106,429 @@ impl WikipediaAnalysis {
             groups.push(next_article_stack);
             depth -= 1;
         }
-        return groups;
+        return Ok(groups);
+    }
+
+    /// Gets the strongly connected components of the link graph with Tarjan's algorithm.
+    ///
+    /// The depth first search keeps its own stack of (article, position in its links) rather than
+    /// recursing, as paths through the full graph are far deeper than the call stack allows.
+    ///
+    /// # Returns
+    /// The component of each article. Components are numbered in the order they are completed, so a
+    /// component only links to components numbered lower than it.
+    ///
+    pub fn get_strongly_connected_components(&self) -> Components {
+        // Order each article was first reached in and the lowest order reachable from it through the
+        // articles on the stack
+        let mut order: Vec<u32> = vec![UNVISITED; self.graph.len()];
+        let mut low: Vec<u32> = vec![UNVISITED; self.graph.len()];
+        let mut on_stack: Vec<bool> = vec![false; self.graph.len()];
+        let mut stack: Vec<u32> = Vec::new();
+        let mut component_of: Vec<u32> = vec![UNVISITED; self.graph.len()];
+        let mut sizes: Vec<u32> = Vec::new();
+
+        let mut next_order: u32 = 0;
+        let mut search: Vec<(u32, usize)> = Vec::new();
+        for root in self.articles() {
+            if order[root as usize] != UNVISITED {
+                continue;
+            }
+            order[root as usize] = next_order;
+            low[root as usize] = next_order;
+            next_order += 1;
+            stack.push(root);
+            on_stack[root as usize] = true;
+            search.push((root, 0));
+
+            while let Some((article, position)) = search.last_mut() {
+                let article = *article;
+                let links = self.graph.outgoing(article);
+                if *position < links.len() {
+                    let next_article = links[*position];
+                    *position += 1;
+                    if self.is_ignored(next_article) {
+                        continue;
+                    }
+                    if order[next_article as usize] == UNVISITED {
+                        order[next_article as usize] = next_order;
+                        low[next_article as usize] = next_order;
+                        next_order += 1;
+                        stack.push(next_article);
+                        on_stack[next_article as usize] = true;
+                        search.push((next_article, 0));
+                    }
+                    else if on_stack[next_article as usize] {
+                        low[article as usize] = cmp::min(low[article as usize], order[next_article as usize]);
+                    }
+                    continue;
+                }
+
+                // All the links of the article are searched
+                search.pop();
+                if let Some((parent, _)) = search.last() {
+                    low[*parent as usize] = cmp::min(low[*parent as usize], low[article as usize]);
+                }
+                if low[article as usize] == order[article as usize] {
+                    let component = sizes.len() as u32;
+                    let mut size = 0;
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member as usize] = false;
+                        component_of[member as usize] = component;
+                        size += 1;
+                        if member == article {
+                            break;
+                        }
+                    }
+                    sizes.push(size);
+                }
+            }
+        }
+        return Components { component_of, sizes };
+    }
+
+    /// Marks the articles reachable from some articles with a breadth first search.
+    ///
+    /// # Arguments
+    /// * `roots` - Articles to search from, which are marked as reachable
+    /// * `direction` - Direction of the links followed
+    /// * `allowed` - Whether the search may step to an article
+    ///
+    /// # Returns
+    /// A mask of the articles reached, indexed by article index
+    ///
+    fn mark_reachable<F: Fn(u32) -> bool>(&self, roots: Vec<u32>, direction: Direction, allowed: F) -> Vec<bool> {
+        let mut reached: Vec<bool> = vec![false; self.graph.len()];
+        for article in roots.iter() {
+            reached[*article as usize] = true;
+        }
+        let mut frontier = roots;
+        while frontier.len() > 0 {
+            let mut next_frontier: Vec<u32> = Vec::new();
+            for article in frontier {
+                for next_article in self.links(article, direction) {
+                    if !reached[next_article as usize] && allowed(next_article) {
+                        reached[next_article as usize] = true;
+                        next_frontier.push(next_article);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+        return reached;
+    }
+
+    /// Gets the weakly connected components of the link graph, ie treating links as undirected.
+    ///
+    /// Components are found with a union find over the links of each article in one direction, as each
+    /// link joins the same two articles in either direction, so the search does not need the transpose.
+    ///
+    /// # Returns
+    /// The component of each article. Components are numbered in order of their lowest article index.
+    ///
+    pub fn get_weakly_connected_components(&self) -> Components {
+        // Root of the set of each article, the lowest article index in the set once all links are joined
+        let mut roots: Vec<u32> = (0..self.graph.len() as u32).collect();
+        let find = |roots: &mut Vec<u32>, mut article: u32| -> u32 {
+            // Path halving, pointing articles at their grandparents on the way up
+            while roots[article as usize] != article {
+                roots[article as usize] = roots[roots[article as usize] as usize];
+                article = roots[article as usize];
+            }
+            return article;
+        };
+        for article in self.articles() {
+            for next_article in self.links(article, Direction::Outgoing) {
+                let root = find(&mut roots, article);
+                let next_root = find(&mut roots, next_article);
+                if root != next_root {
+                    roots[cmp::max(root, next_root) as usize] = cmp::min(root, next_root);
+                }
+            }
+        }
+
+        let mut component_of: Vec<u32> = vec![UNVISITED; self.graph.len()];
+        let mut sizes: Vec<u32> = Vec::new();
+        for article in self.articles() {
+            let root = find(&mut roots, article);
+            if root == article {
+                component_of[article as usize] = sizes.len() as u32;
+                sizes.push(0);
+            }
+            else {
+                component_of[article as usize] = component_of[root as usize];
+            }
+            sizes[component_of[article as usize] as usize] += 1;
+        }
+        return Components { component_of, sizes };
+    }
+
+    /// Classifies every article into the bow-tie structure of the link graph around its largest
+    /// strongly connected component, as described by Broder et al in "Graph structure in the web".
+    ///
+    /// # Arguments
+    /// * `components` - The strongly connected components, see `get_strongly_connected_components()`
+    ///
+    /// # Returns
+    /// The region of each article, indexed by article index. Ignored articles are in no region.
+    ///
+    pub fn get_bow_tie(&self, components: &Components) -> Vec<Option<BowTieRegion>> {
+        let mut regions: Vec<Option<BowTieRegion>> = vec![None; self.graph.len()];
+        let giant = match components.largest() {
+            Some(giant) => giant,
+            None => return regions
+        };
+        let giant_articles: Vec<u32> = self.articles().filter(|x| components.component_of[*x as usize] == giant).collect();
+
+        let to_giant = self.mark_reachable(giant_articles.clone(), Direction::Incoming, |_| true);
+        let from_giant = self.mark_reachable(giant_articles, Direction::Outgoing, |_| true);
+        for article in self.articles() {
+            let index = article as usize;
+            regions[index] = Some(match (to_giant[index], from_giant[index]) {
+                (true, true) => BowTieRegion::GiantComponent,
+                (true, false) => BowTieRegion::In,
+                (false, true) => BowTieRegion::Out,
+                (false, false) => BowTieRegion::Disconnected
+            });
+        }
+
+        // Tendrils and tubes are the articles outside the bow-tie that are connected to its in or out regions
+        let outside = |article: u32| regions[article as usize] == Some(BowTieRegion::Disconnected);
+        let in_articles: Vec<u32> = self.articles().filter(|x| regions[*x as usize] == Some(BowTieRegion::In)).collect();
+        let out_articles: Vec<u32> = self.articles().filter(|x| regions[*x as usize] == Some(BowTieRegion::Out)).collect();
+        let from_in = self.mark_reachable(in_articles, Direction::Outgoing, outside);
+        let to_out = self.mark_reachable(out_articles, Direction::Incoming, outside);
+        for article in self.articles() {
+            let index = article as usize;
+            if regions[index] != Some(BowTieRegion::Disconnected) {
+                continue;
+            }
+            regions[index] = Some(match (from_in[index], to_out[index]) {
+                (true, true) => BowTieRegion::Tube,
+                (true, false) | (false, true) => BowTieRegion::Tendril,
+                (false, false) => BowTieRegion::Disconnected
+            });
+        }
+        return regions;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::metadata::Metadata;
+    use crate::parse::Article;
+
+    /// Alpha -> Beta -> Gamma -> Delta, with Alpha -> Epsilon -> Delta as a second route,
+    /// and Zeta linking to nothing
+    fn chain() -> WikipediaAnalysis {
+        let titles = ["Alpha", "Beta", "Gamma", "Delta", "Epsilon", "Zeta"];
+        let outgoing: [&[u32]; 6] = [&[1, 4], &[2], &[3], &[], &[3], &[]];
+        let article_map: HashMap<String, u32> = titles
+            .iter()
+            .enumerate()
+            .map(|(index, title)| (title.to_string(), index as u32))
+            .collect();
+        let mut articles: Vec<Article> = outgoing.iter().map(|x| Article { links: x.to_vec() }).collect();
+        let metadata = Metadata::new(ParserMode::OutgoingLinks);
+        let graph = LinkGraph::from_dataset("test", &metadata, &article_map, &mut articles, &HashMap::new()).unwrap();
+        return WikipediaAnalysis::new(graph);
+    }
+
+    /// Graph of articles named by their index
+    fn numbered(outgoing: Vec<Vec<u32>>) -> WikipediaAnalysis {
+        let article_map: HashMap<String, u32> = (0..outgoing.len() as u32).map(|x| (format!("Article {}", x), x)).collect();
+        let mut articles: Vec<Article> = outgoing.into_iter().map(|links| Article { links }).collect();
+        let graph = LinkGraph::from_dataset(
+            "test", &Metadata::new(ParserMode::OutgoingLinks), &article_map, &mut articles, &HashMap::new()
+        ).unwrap();
+        return WikipediaAnalysis::new(graph);
+    }
+
+    #[test]
+    fn finds_shortest_paths_from_both_ends() {
+        let analysis = chain();
+        let steps = |start, destination, direction| {
+            return analysis.get_number_of_steps_between_articles(start, destination, direction).unwrap();
+        };
+        let path = |start, destination, direction| {
+            return analysis.get_path_between_articles(start, destination, direction).unwrap();
+        };
+
+        assert_eq!(steps(0, 3, Direction::Incoming), Some(2));
+        assert_eq!(path(0, 3, Direction::Incoming), Some(vec![0, 4, 3]));
+        assert_eq!(steps(1, 3, Direction::Incoming), Some(2));
+        assert_eq!(path(1, 3, Direction::Incoming), Some(vec![1, 2, 3]));
+        assert_eq!(path(0, 1, Direction::Incoming), Some(vec![0, 1]));
+        assert_eq!(path(2, 2, Direction::Incoming), Some(vec![2]));
+        assert_eq!(steps(2, 2, Direction::Incoming), Some(0));
+
+        // Searching outgoing links follows the links from the destination to the start
+        assert_eq!(path(3, 0, Direction::Outgoing), Some(vec![3, 4, 0]));
+        assert_eq!(steps(3, 0, Direction::Incoming), None);
+        assert_eq!(path(5, 0, Direction::Incoming), None);
+    }
+
+    #[test]
+    fn counts_all_shortest_paths() {
+        let analysis = chain();
+        let all_paths = |start, destination, max_paths| {
+            return analysis.get_all_paths_between_articles(start, destination, Direction::Incoming, max_paths).unwrap();
+        };
+
+        // Alpha reaches Delta in 2 steps through Epsilon, the route through Beta and Gamma is longer
+        let paths = all_paths(0, 3, 10).unwrap();
+        assert_eq!(paths.steps, 2);
+        assert_eq!(paths.count, 1);
+        assert_eq!(paths.paths, vec![vec![0, 4, 3]]);
+        assert_eq!(paths.through, vec![(4, 1)]);
+
+        assert_eq!(all_paths(5, 3, 10), None);
+        assert_eq!(all_paths(2, 2, 10).unwrap().paths, vec![vec![2]]);
+        assert_eq!(all_paths(2, 2, 0).unwrap().paths.len(), 0);
+    }
+
+    #[test]
+    fn counts_paths_through_a_diamond() {
+        // Each of the 3 layers of 2 articles links to both articles of the next layer
+        let mut outgoing: Vec<Vec<u32>> = vec![vec![1, 2]];
+        for layer in 0..3 {
+            let next: Vec<u32> = match layer {
+                2 => vec![7],
+                _ => vec![3 + 2 * layer, 4 + 2 * layer]
+            };
+            outgoing.push(next.clone());
+            outgoing.push(next);
+        }
+        outgoing.push(Vec::new());
+        let analysis = numbered(outgoing);
+
+        let paths = analysis.get_all_paths_between_articles(0, 7, Direction::Incoming, 3).unwrap().unwrap();
+        assert_eq!(paths.steps, 4);
+        assert_eq!(paths.count, 8);
+        assert_eq!(paths.paths, vec![vec![0, 1, 3, 5, 7], vec![0, 1, 3, 6, 7], vec![0, 1, 4, 5, 7]]);
+        assert_eq!(paths.through.len(), 6);
+        assert!(paths.through.iter().all(|x| x.1 == 4));
+
+        // Searching outgoing links finds the same paths from the other end
+        let reverse = analysis.get_all_paths_between_articles(7, 0, Direction::Outgoing, 0).unwrap().unwrap();
+        assert_eq!(reverse.count, 8);
+    }
+
+    #[test]
+    fn finds_k_shortest_simple_paths() {
+        let analysis = chain();
+        let k_paths = |start, destination, direction, k, budget| {
+            return analysis.get_k_shortest_paths_between_articles(start, destination, direction, k, budget).unwrap();
+        };
+
+        let paths = k_paths(0, 3, Direction::Incoming, 5, SearchBudget::default());
+        assert_eq!(paths.paths, vec![vec![0, 4, 3], vec![0, 1, 2, 3]]);
+        assert!(!paths.exhausted);
+        assert_eq!(k_paths(0, 3, Direction::Incoming, 1, SearchBudget::default()).paths, vec![vec![0, 4, 3]]);
+        assert_eq!(k_paths(3, 0, Direction::Outgoing, 5, SearchBudget::default()).paths.len(), 2);
+        assert_eq!(k_paths(5, 3, Direction::Incoming, 5, SearchBudget::default()).paths.len(), 0);
+        assert_eq!(k_paths(0, 3, Direction::Incoming, 0, SearchBudget::default()).paths.len(), 0);
+
+        // The first path does not count against the budget
+        let budget = SearchBudget { max_expansions: Some(0), time_limit: None };
+        let paths = k_paths(0, 3, Direction::Incoming, 5, budget);
+        assert_eq!(paths.paths, vec![vec![0, 4, 3]]);
+        assert!(paths.exhausted);
+    }
+
+    #[test]
+    fn constrains_paths() {
+        let analysis = chain();
+        let constrained = |start, destination, constraints: PathConstraints| {
+            let steps = analysis
+                .get_constrained_number_of_steps_between_articles(start, destination, Direction::Incoming, &constraints)
+                .unwrap();
+            let path = analysis
+                .get_constrained_path_between_articles(start, destination, Direction::Incoming, &constraints)
+                .unwrap();
+            assert_eq!(steps, path.as_ref().map(|x| x.len() as u32 - 1));
+            return path;
+        };
+
+        assert_eq!(constrained(0, 3, PathConstraints::default()), Some(vec![0, 4, 3]));
+        let avoid = PathConstraints { avoid: vec![4], ..PathConstraints::default() };
+        assert_eq!(constrained(0, 3, avoid), Some(vec![0, 1, 2, 3]));
+        let via = PathConstraints { via: vec![2], ..PathConstraints::default() };
+        assert_eq!(constrained(0, 3, via), Some(vec![0, 1, 2, 3]));
+        let via_unreachable = PathConstraints { via: vec![5], ..PathConstraints::default() };
+        assert_eq!(constrained(0, 3, via_unreachable), None);
+
+        // Beta, Gamma and Epsilon each have 2 links. The start and destination are never left out
+        let max_degree = PathConstraints { max_degree: Some(1), ..PathConstraints::default() };
+        assert_eq!(constrained(0, 3, max_degree), None);
+        let max_degree = PathConstraints { max_degree: Some(1), ..PathConstraints::default() };
+        assert_eq!(constrained(0, 1, max_degree), Some(vec![0, 1]));
+
+        // Avoiding an endpoint does not stop it being reached
+        let avoid = PathConstraints { avoid: vec![0, 3], ..PathConstraints::default() };
+        assert_eq!(constrained(0, 3, avoid), Some(vec![0, 4, 3]));
+    }
+
+    #[test]
+    fn finds_strongly_connected_components_and_bow_tie() {
+        // 0, 1, 2 are a cycle, 3 links into it and 4 is linked from it. 5 hangs off 3, 6 goes from 3 to 4,
+        // 7 links to 4 and 8, 9 link only to each other
+        let analysis = numbered(vec![
+            vec![1], vec![2], vec![0, 4], vec![0, 5, 6], vec![], vec![], vec![4], vec![4], vec![9], vec![8]
+        ]);
+        let components = analysis.get_strongly_connected_components();
+        let giant = components.largest().unwrap();
+        assert_eq!(components.sizes[giant as usize], 3);
+        assert_eq!(components.component_of[0], giant);
+        assert_eq!(components.component_of[1], giant);
+        assert_eq!(components.component_of[8], components.component_of[9]);
+        assert_eq!(components.size_distribution(), vec![(1, 5), (2, 1), (3, 1)]);
+        // Components only link to lower numbered components
+        assert!(components.component_of[4] < giant && giant < components.component_of[3]);
+
+        use BowTieRegion::*;
+        let regions: Vec<BowTieRegion> = analysis.get_bow_tie(&components).into_iter().map(|x| x.unwrap()).collect();
+        assert_eq!(regions, vec![
+            GiantComponent, GiantComponent, GiantComponent, In, Out, Tendril, Tube, Tendril, Disconnected, Disconnected
+        ]);
+    }
+
+    #[test]
+    fn finds_weakly_connected_components() {
+        // 0 and 2 link to 1, 3 links to 4, 5 links to nothing and 6 links to 5 through 7
+        let mut analysis = numbered(vec![vec![1], vec![], vec![1], vec![4], vec![], vec![], vec![7], vec![5]]);
+        let components = analysis.get_weakly_connected_components();
+        assert_eq!(components.component_of, vec![0, 0, 0, 1, 1, 2, 2, 2]);
+        assert_eq!(components.sizes, vec![3, 2, 3]);
+        assert_eq!(components.largest(), Some(0));
+
+        // Ignoring 1 splits 0 and 2
+        analysis.ignore_articles(&IgnoreRules::from_lines("test", "Article 1\n").unwrap());
+        let components = analysis.get_weakly_connected_components();
+        assert_eq!(components.component_of, vec![0, UNVISITED, 1, 2, 2, 3, 3, 3]);
+        assert_eq!(components.size_distribution(), vec![(1, 2), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn strongly_connected_components_of_a_long_cycle() {
+        // Deep enough to overflow the stack if the search recursed
+        let count = 200_000;
+        let analysis = numbered((0..count).map(|x| vec![(x + 1) % count]).collect());
+        let components = analysis.get_strongly_connected_components();
+        assert_eq!(components.sizes, vec![count]);
+    }
+
+    #[test]
+    fn paths_avoid_ignored_articles() {
+        let mut analysis = chain();
+        analysis.ignore_articles(&IgnoreRules::from_lines("test", "Epsilon\n").unwrap());
+
+        assert_eq!(analysis.get_path_between_articles(0, 3, Direction::Incoming), Ok(Some(vec![0, 1, 2, 3])));
+        assert_eq!(
+            analysis.get_path_between_articles(0, 4, Direction::Incoming),
+            Err(AnalysisError::ArticleIgnored(4)));
     }
 }
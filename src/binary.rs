@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::*;
+use crate::error::{ParseError, ParseResult};
+use std::ops::Range;
+use crate::parse::{self, Article, Dataset, ParserMode};
+use crate::graph::LinkGraph;
+use crate::metadata::Metadata;
+
+/// Magic bytes at the start of a binary dataset
+pub const MAGIC: &[u8; 8] = b"WIKIGRPH";
+/// Version of the binary format written by `write_binary()`, other versions are rejected
+pub const VERSION: u32 = 1;
+/// Size of the header in bytes
+pub const HEADER_SIZE: u64 = 64;
+/// Every section starts on a multiple of this many bytes
+const SECTION_ALIGNMENT: u64 = 8;
+
+/// Header of a binary dataset.
+///
+/// The binary format stores the link graph in compressed sparse row form: the links of every
+/// article are stored back to back in one flat neighbor array, with an offsets array giving the
+/// position of the first link of each article. Titles are stored the same way in a string table.
+/// The layout is designed to be used directly from a memory mapped file, see `graph::LinkGraph`.
+///
+/// Both directions of the links are stored: the links in the direction the dump was parsed with
+/// (`mode`) and their transpose, so incoming and outgoing links are available from one file.
+///
+/// All integers are little endian. The file layout is:
+///
+/// | Section                  | Contents                                                   |
+/// |--------------------------|------------------------------------------------------------|
+/// | Header                   | Magic `WIKIGRPH`, version, mode and the section sizes below |
+/// | Link offsets             | `article_count + 1` u64, links of article i are `[offsets[i], offsets[i + 1])` |
+/// | Neighbors                | `link_count` u32 article indices                           |
+/// | Reverse link offsets     | `article_count + 1` u64, as link offsets for the transposed links |
+/// | Reverse neighbors        | `link_count` u32 article indices                           |
+/// | Title offsets            | `article_count + 1` u64 byte offsets into the title table  |
+/// | Titles                   | `title_bytes` bytes of UTF-8 titles in article index order |
+/// | Title order              | `article_count` u32 article indices sorted by title        |
+/// | Redirect targets         | `redirect_count` u32 article indices                       |
+/// | Redirect title offsets   | `redirect_count + 1` u64 byte offsets into the redirect title table |
+/// | Redirect titles          | `redirect_title_bytes` bytes of UTF-8 redirect names, sorted |
+/// | Metadata                 | `metadata_bytes` bytes of UTF-8 `metadata::Metadata` lines |
+///
+/// Each section is padded with zeros to a multiple of 8 bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub version: u32,
+    /// Direction of the links in the link sections, the reverse link sections hold the other direction
+    pub mode: ParserMode,
+    pub article_count: u64,
+    pub link_count: u64,
+    pub title_bytes: u64,
+    pub redirect_count: u64,
+    pub redirect_title_bytes: u64,
+    pub metadata_bytes: u64
+}
+
+impl Header {
+
+    /// Reads and checks a header from the start of a dataset
+    pub fn read(bytes: &[u8], path: &str) -> ParseResult<Header> {
+        if bytes.len() < HEADER_SIZE as usize {
+            return Err(ParseError::format(path, "File too short for header"));
+        }
+        if &bytes[0..8] != MAGIC {
+            return Err(ParseError::format(path, "Not a binary dataset"));
+        }
+
+        let u64_at = |position: usize| u64::from_le_bytes(bytes[position..position + 8].try_into().unwrap());
+        let mode = match u32::from_le_bytes(bytes[12..16].try_into().unwrap()) {
+            0 => ParserMode::IncomingLinks,
+            1 => ParserMode::OutgoingLinks,
+            x => return Err(ParseError::format(path, format!("Invalid mode {}", x)))
+        };
+        let header = Header {
+            version: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            mode,
+            article_count: u64_at(16),
+            link_count: u64_at(24),
+            title_bytes: u64_at(32),
+            redirect_count: u64_at(40),
+            redirect_title_bytes: u64_at(48),
+            metadata_bytes: u64_at(56)
+        };
+        if header.version != VERSION {
+            return Err(ParseError::format(path, format!(
+                "Unsupported version {} (only version {} is supported)", header.version, VERSION)));
+        }
+        return Ok(header);
+    }
+
+    /// Writes the header
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        let mode: u32 = match self.mode {
+            ParserMode::IncomingLinks => 0,
+            ParserMode::OutgoingLinks => 1
+        };
+        writer.write_all(&mode.to_le_bytes())?;
+        writer.write_all(&self.article_count.to_le_bytes())?;
+        writer.write_all(&self.link_count.to_le_bytes())?;
+        writer.write_all(&self.title_bytes.to_le_bytes())?;
+        writer.write_all(&self.redirect_count.to_le_bytes())?;
+        writer.write_all(&self.redirect_title_bytes.to_le_bytes())?;
+        writer.write_all(&self.metadata_bytes.to_le_bytes())?;
+        return Ok(());
+    }
+}
+
+/// Byte ranges of the sections of a binary dataset, see `Header` for the layout
+#[derive(Debug, Clone)]
+pub struct Sections {
+    pub offsets: Range<usize>,
+    pub neighbors: Range<usize>,
+    pub reverse_offsets: Range<usize>,
+    pub reverse_neighbors: Range<usize>,
+    pub title_offsets: Range<usize>,
+    pub titles: Range<usize>,
+    pub title_order: Range<usize>,
+    pub redirect_targets: Range<usize>,
+    pub redirect_title_offsets: Range<usize>,
+    pub redirect_titles: Range<usize>,
+    pub metadata: Range<usize>,
+    /// Total size of the file
+    pub end: usize
+}
+
+impl Sections {
+
+    /// Works out where the sections of a dataset are from its header.
+    ///
+    /// # Errors
+    /// Returns an error if the sizes in the header do not match the size of the file
+    ///
+    pub fn new(header: &Header, file_size: u64, path: &str) -> ParseResult<Sections> {
+        // Section sizes come from the file so may be corrupt, use checked arithmetic
+        // and compare against the file size before any section is used
+        let mut position: u64 = HEADER_SIZE;
+        let mut section = |size: Option<u64>| -> Option<Range<usize>> {
+            let start = position;
+            let end = start.checked_add(size?)?;
+            position = start.checked_add(padded(size?)?)?;
+            return Some(start as usize..end as usize);
+        };
+        let u64s = |count: u64| count.checked_add(1)?.checked_mul(8);
+        let u32s = |count: u64| count.checked_mul(4);
+
+        let too_large = || ParseError::format(path, "Section sizes in header are too large");
+        let offsets = section(u64s(header.article_count)).ok_or_else(too_large)?;
+        let neighbors = section(u32s(header.link_count)).ok_or_else(too_large)?;
+        let reverse_offsets = section(u64s(header.article_count)).ok_or_else(too_large)?;
+        let reverse_neighbors = section(u32s(header.link_count)).ok_or_else(too_large)?;
+        let title_offsets = section(u64s(header.article_count)).ok_or_else(too_large)?;
+        let titles = section(Some(header.title_bytes)).ok_or_else(too_large)?;
+        let title_order = section(u32s(header.article_count)).ok_or_else(too_large)?;
+        let redirect_targets = section(u32s(header.redirect_count)).ok_or_else(too_large)?;
+        let redirect_title_offsets = section(u64s(header.redirect_count)).ok_or_else(too_large)?;
+        let redirect_titles = section(Some(header.redirect_title_bytes)).ok_or_else(too_large)?;
+        let metadata = section(Some(header.metadata_bytes)).ok_or_else(too_large)?;
+
+        if position != file_size {
+            return Err(ParseError::format(path, format!(
+                "Expected {} bytes but file is {} bytes", position, file_size)));
+        }
+        return Ok(Sections {
+            offsets,
+            neighbors,
+            reverse_offsets,
+            reverse_neighbors,
+            title_offsets,
+            titles,
+            title_order,
+            redirect_targets,
+            redirect_title_offsets,
+            redirect_titles,
+            metadata,
+            end: position as usize
+        });
+    }
+}
+
+/// Rounds a section size up to the section alignment
+fn padded(size: u64) -> Option<u64> {
+    return size.div_ceil(SECTION_ALIGNMENT).checked_mul(SECTION_ALIGNMENT);
+}
+
+/// Writes the zeros needed to pad a section of the given size
+fn write_padding<W: Write>(writer: &mut W, size: u64) -> Result<()> {
+    let padding = (size.div_ceil(SECTION_ALIGNMENT) * SECTION_ALIGNMENT - size) as usize;
+    return writer.write_all(&[0u8; SECTION_ALIGNMENT as usize][..padding]);
+}
+
+/// Transposes links in compressed sparse row form, eg turns incoming links into outgoing links.
+///
+/// # Arguments
+/// * `article_count` - Number of articles
+/// * `links` - Links of each article in index order, must be `article_count` long
+///
+/// # Returns
+/// A tuple of (offsets, neighbors) of the transposed links, see `Header`.
+/// The links of each article are sorted as the articles are visited in index order.
+///
+pub fn transpose<'a, I>(article_count: usize, links: I) -> (Vec<u64>, Vec<u32>)
+    where I: Iterator<Item = &'a [u32]> + Clone {
+
+    // Count the links to each article, then place each link after the ones counted before it
+    let mut offsets: Vec<u64> = vec![0; article_count + 1];
+    for article_links in links.clone() {
+        for link in article_links.iter() {
+            offsets[*link as usize + 1] += 1;
+        }
+    }
+    for index in 0..article_count {
+        offsets[index + 1] += offsets[index];
+    }
+
+    let mut next: Vec<u64> = offsets[..article_count].to_vec();
+    let mut neighbors: Vec<u32> = vec![0; offsets[article_count] as usize];
+    for (article, article_links) in links.enumerate() {
+        for link in article_links.iter() {
+            neighbors[next[*link as usize] as usize] = article as u32;
+            next[*link as usize] += 1;
+        }
+    }
+    return (offsets, neighbors);
+}
+
+/// Checks if a file is a binary dataset by looking for the magic bytes.
+///
+/// # Arguments
+/// * `path` - Path to the dataset
+///
+pub fn is_binary(path: &str) -> ParseResult<bool> {
+    let mut file = File::open(path).map_err(|e| ParseError::io(path, e))?;
+    let mut magic = [0u8; 8];
+    return match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(ParseError::io(path, e))
+    };
+}
+
+/// Writes the values returned by `parse_xml_dump()` to a binary dataset, see `Header` for the format.
+///
+/// Links of each article are sorted and duplicates removed, as with `parse::write_to_tsv()`.
+/// The transpose of the links is computed and written too.
+///
+/// # Arguments
+/// * `output_path` - File path to write the dataset to
+/// * `metadata` - Metadata of the dataset, including the direction of the links in `articles`.
+///   The article and link counts are filled in
+/// * `article_map` - Hashmap of article name -> article index
+/// * `articles` - Adjacency list representation of links graph
+/// * `redirects_map` - Hashmap of redirect name -> article index
+///
+pub fn write_binary(
+    output_path: &str,
+    metadata: &Metadata,
+    article_map: &HashMap<String, u32>,
+    articles: &mut [Article],
+    redirects_map: &HashMap<String, u32>) -> ParseResult<()> {
+
+    let file = File::create(output_path).map_err(|e| ParseError::io(output_path, e))?;
+    let mut writer = BufWriter::new(file);
+    write_binary_to(&mut writer, output_path, metadata, article_map, articles, redirects_map)?;
+    return writer.flush().map_err(|e| ParseError::io(output_path, e));
+}
+
+/// Writes a binary dataset to a writer, see `write_binary()`.
+///
+/// # Arguments
+/// * `writer` - Writer to write the dataset to
+/// * `output_path` - Name of the output, used in errors
+/// * `metadata` - Metadata of the dataset, see `write_binary()`
+/// * `article_map` - Hashmap of article name -> article index
+/// * `articles` - Adjacency list representation of links graph
+/// * `redirects_map` - Hashmap of redirect name -> article index
+///
+pub fn write_binary_to<W: Write>(
+    writer: &mut W,
+    output_path: &str,
+    metadata: &Metadata,
+    article_map: &HashMap<String, u32>,
+    articles: &mut [Article],
+    redirects_map: &HashMap<String, u32>) -> ParseResult<()> {
+
+    if article_map.len() != articles.len() {
+        return Err(ParseError::format(output_path, "Number of titles does not match number of articles"));
+    }
+
+    let article_titles = parse::title_table(article_map, articles.len(), output_path)?;
+    let mut title_order: Vec<(&str, u32)> = article_map.iter().map(|(title, index)| (title.as_str(), *index)).collect();
+    // Titles are unique so sorting by title alone gives a stable order
+    title_order.sort_unstable();
+
+    for article in articles.iter_mut() {
+        article.links.sort_unstable();
+        article.links.dedup();
+    }
+    if articles.iter().any(|x| x.links.iter().any(|link| *link as usize >= articles.len())) {
+        return Err(ParseError::format(output_path, "Link to an article index out of range"));
+    }
+    let (reverse_offsets, reverse_neighbors) = transpose(articles.len(), articles.iter().map(|x| &x.links[..]));
+
+    let mut redirects: Vec<(&String, &u32)> = redirects_map.iter().collect();
+    redirects.sort_unstable();
+
+    let mut metadata = metadata.clone();
+    metadata.article_count = articles.len() as u64;
+    metadata.link_count = articles.iter().map(|x| x.links.len() as u64).sum();
+    let metadata_text: String = metadata.to_lines().iter().map(|x| format!("{}\n", x)).collect();
+
+    let header = Header {
+        version: VERSION,
+        mode: metadata.mode,
+        article_count: metadata.article_count,
+        link_count: metadata.link_count,
+        title_bytes: article_map.keys().map(|x| x.len() as u64).sum(),
+        redirect_count: redirects.len() as u64,
+        redirect_title_bytes: redirects.iter().map(|x| x.0.len() as u64).sum(),
+        metadata_bytes: metadata_text.len() as u64
+    };
+
+    let mut write = || -> Result<()> {
+        header.write(writer)?;
+
+        // Link offsets and neighbors
+        let mut offset: u64 = 0;
+        writer.write_all(&offset.to_le_bytes())?;
+        for article in articles.iter() {
+            offset += article.links.len() as u64;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for article in articles.iter() {
+            for link in article.links.iter() {
+                writer.write_all(&link.to_le_bytes())?;
+            }
+        }
+        write_padding(writer, 4 * header.link_count)?;
+
+        // Reverse link offsets and neighbors
+        for offset in reverse_offsets.iter() {
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for link in reverse_neighbors.iter() {
+            writer.write_all(&link.to_le_bytes())?;
+        }
+        write_padding(writer, 4 * header.link_count)?;
+
+        // Title table
+        let mut offset: u64 = 0;
+        writer.write_all(&offset.to_le_bytes())?;
+        for title in article_titles.iter() {
+            offset += title.len() as u64;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for title in article_titles.iter() {
+            writer.write_all(title.as_bytes())?;
+        }
+        write_padding(writer, header.title_bytes)?;
+        for (_, article_index) in title_order.iter() {
+            writer.write_all(&article_index.to_le_bytes())?;
+        }
+        write_padding(writer, 4 * header.article_count)?;
+
+        // Redirect table
+        for (_, article_index) in redirects.iter() {
+            writer.write_all(&article_index.to_le_bytes())?;
+        }
+        write_padding(writer, 4 * header.redirect_count)?;
+        let mut offset: u64 = 0;
+        writer.write_all(&offset.to_le_bytes())?;
+        for (redirect_name, _) in redirects.iter() {
+            offset += redirect_name.len() as u64;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for (redirect_name, _) in redirects.iter() {
+            writer.write_all(redirect_name.as_bytes())?;
+        }
+        write_padding(writer, header.redirect_title_bytes)?;
+
+        writer.write_all(metadata_text.as_bytes())?;
+        write_padding(writer, header.metadata_bytes)?;
+        return Ok(());
+    };
+
+    return write().map_err(|e| ParseError::io(output_path, e));
+}
+
+/// Loads a binary dataset (produced by `write_binary()`) into the representation used while parsing.
+///
+/// # Arguments
+/// * `path` - Path to the dataset
+/// * `mode` - Direction of the links to load
+///
+/// # Returns
+/// The dataset in the same representation as returned by `parse::load_from_tsv()`
+///
+/// # Errors
+/// Returns an error if the file is not a binary dataset, is an unsupported version or is corrupted
+///
+pub fn load_binary(path: &str, mode: ParserMode) -> ParseResult<Dataset> {
+    return LinkGraph::open(path, mode)?.to_dataset(path, mode);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_xml_dump, ParseOptions};
+    use crate::graph::Direction;
+
+    #[test]
+    fn round_trips_dataset() {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let (article_map, mut articles, redirects_map) = parse_xml_dump(&fixture, ParseOptions::default()).unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("round-trip-{}.graph", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        write_binary(&path, &Metadata::new(ParserMode::IncomingLinks), &article_map, &mut articles, &redirects_map).unwrap();
+        let loaded = load_binary(&path, ParserMode::IncomingLinks);
+        let graph = LinkGraph::open(&path, ParserMode::OutgoingLinks);
+        std::fs::remove_file(&path).unwrap();
+
+        let (loaded_article_map, loaded_articles, loaded_redirects_map) = loaded.unwrap();
+        assert_eq!(loaded_article_map, article_map);
+        assert_eq!(loaded_redirects_map, redirects_map);
+        let links: Vec<&Vec<u32>> = articles.iter().map(|x| &x.links).collect();
+        let loaded_links: Vec<&Vec<u32>> = loaded_articles.iter().map(|x| &x.links).collect();
+        assert_eq!(loaded_links, links);
+
+        let graph = graph.unwrap();
+        for (title, index) in article_map.iter() {
+            assert_eq!(graph.find_title(title), Some(*index));
+            assert_eq!(graph.title(*index), title);
+            assert_eq!(graph.incoming(*index), &articles[*index as usize].links[..]);
+        }
+        for (name, index) in redirects_map.iter() {
+            assert_eq!(graph.find_redirect(name), Some(*index));
+        }
+        assert_eq!(graph.find_title("Missing page"), None);
+        assert_eq!(graph.find_redirect("Loop A"), None);
+        assert_eq!(graph.header().mode, ParserMode::IncomingLinks);
+        assert_eq!(graph.metadata().article_count, articles.len() as u64);
+        assert_eq!(graph.metadata().link_count, graph.link_count());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let (article_map, mut articles, redirects_map) = parse_xml_dump(&fixture, ParseOptions::default()).unwrap();
+        let mut bytes: Vec<u8> = Vec::new();
+        let metadata = Metadata::new(ParserMode::IncomingLinks);
+        write_binary_to(&mut bytes, "test", &metadata, &article_map, &mut articles, &redirects_map).unwrap();
+        assert_eq!(Header::read(&bytes, "test").unwrap().version, VERSION);
+
+        for version in [0u32, 2, 4].iter() {
+            bytes[8..12].copy_from_slice(&version.to_le_bytes());
+            assert!(Header::read(&bytes, "test").is_err());
+        }
+    }
+
+    #[test]
+    fn stores_both_directions() {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let options = ParseOptions {
+            mode: ParserMode::OutgoingLinks,
+            ..ParseOptions::default()
+        };
+        let (article_map, mut articles, redirects_map) = parse_xml_dump(&fixture, options).unwrap();
+        let graph = LinkGraph::from_dataset("test", &Metadata::new(ParserMode::OutgoingLinks), &article_map, &mut articles, &redirects_map)
+            .unwrap();
+
+        let alpha = article_map["Alpha"];
+        let united_states = article_map["United States"];
+        assert_eq!(graph.outgoing(alpha), &[united_states]);
+        assert_eq!(graph.incoming(united_states), &[alpha]);
+        assert_eq!(graph.links(alpha, Direction::Incoming), graph.incoming(alpha));
+        assert_eq!(graph.incoming(alpha), &[united_states]);
+
+        assert_eq!(graph.index_of("alpha"), Ok(alpha));
+        assert_eq!(graph.title_of(alpha), Ok("Alpha"));
+        assert!(graph.index_of("Missing page").is_err());
+        assert!(graph.title_of(graph.len() as u32).is_err());
+
+        let (_, incoming, _) = graph.to_dataset("test", ParserMode::IncomingLinks).unwrap();
+        assert_eq!(incoming[united_states as usize].links, vec![alpha]);
+    }
+}
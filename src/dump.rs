@@ -0,0 +1,251 @@
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::*;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Magic bytes at the start of a bzip2 stream ("BZh")
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// Magic bytes at the start of a gzip member
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Buffer size used for reading dumps. The dumps are large and read sequentially
+/// so a larger buffer than the default reduces the number of read syscalls.
+const READ_BUFFER_SIZE: usize = 1 << 20;
+
+/// Compression formats a wikipedia XML dump may be stored in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Bzip2,
+    Gzip
+}
+
+/// Works out the compression format of a dump.
+///
+/// The file extension is checked first (`.bz2`, `.gz`), if the extension is not recognised
+/// the first few bytes of the file are checked for the bzip2 or gzip magic bytes.
+///
+/// # Arguments
+/// * `path` - Path to the dump
+///
+pub fn detect_compression(path: &Path) -> Result<Compression> {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("bz2") => return Ok(Compression::Bzip2),
+        Some("gz") => return Ok(Compression::Gzip),
+        _ => ()
+    }
+
+    let mut magic = [0u8; 3];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n
+        }
+    }
+
+    if magic[..read].starts_with(BZIP2_MAGIC) {
+        return Ok(Compression::Bzip2);
+    }
+    if magic[..read].starts_with(GZIP_MAGIC) {
+        return Ok(Compression::Gzip);
+    }
+    return Ok(Compression::None);
+}
+
+/// Reader that counts the bytes read through it.
+/// Wraps the file of a dump (before decompression) so progress through the file can be reported.
+pub struct CountingReader<R> {
+    inner: R,
+    bytes_read: Arc<AtomicU64>
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R, bytes_read: Arc<AtomicU64>) -> CountingReader<R> {
+        return CountingReader { inner, bytes_read };
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.bytes_read.fetch_add(count as u64, Ordering::Relaxed);
+        return Ok(count);
+    }
+}
+
+/// Opens a wikipedia XML dump for reading, transparently decompressing it if required.
+///
+/// Dumps may be plain XML, bzip2 compressed (`pages-articles.xml.bz2` or the
+/// `pages-articles-multistream.xml.bz2` dump) or gzip compressed. Compressed dumps are
+/// decompressed as a stream so the uncompressed XML never needs to be written to disk.
+///
+/// # Arguments
+/// * `path` - Path to the dump
+///
+pub fn open_dump(path: &Path) -> Result<Box<dyn BufRead>> {
+    return open_dump_counted(path, Arc::new(AtomicU64::new(0)));
+}
+
+/// Opens a wikipedia XML dump for reading, see `open_dump()`, counting the bytes read from the file.
+///
+/// # Arguments
+/// * `path` - Path to the dump
+/// * `bytes_read` - Counter the number of bytes read from the (compressed) file is added to
+///
+pub fn open_dump_counted(path: &Path, bytes_read: Arc<AtomicU64>) -> Result<Box<dyn BufRead>> {
+    let file = CountingReader::new(File::open(path)?, bytes_read);
+    let reader: Box<dyn BufRead> = match detect_compression(path)? {
+        Compression::None => Box::new(BufReader::with_capacity(READ_BUFFER_SIZE, file)),
+        // Multistream dumps are many bzip2 streams concatenated together,
+        // the multi decoders continue on to the next stream instead of stopping at the first
+        Compression::Bzip2 => Box::new(BufReader::with_capacity(
+            READ_BUFFER_SIZE,
+            MultiBzDecoder::new(BufReader::with_capacity(READ_BUFFER_SIZE, file))
+        )),
+        Compression::Gzip => Box::new(BufReader::with_capacity(
+            READ_BUFFER_SIZE,
+            MultiGzDecoder::new(BufReader::with_capacity(READ_BUFFER_SIZE, file))
+        ))
+    };
+    return Ok(reader);
+}
+
+/// Reads the index file that accompanies a `pages-articles-multistream` dump.
+///
+/// Each line of the index is of the form `offset:page id:page title` where offset is the
+/// byte offset into the dump of the bzip2 stream containing the page. Each stream holds up to
+/// 100 pages so many lines share the same offset.
+///
+/// # Arguments
+/// * `index_path` - Path to the `-multistream-index.txt(.bz2)` file
+///
+/// # Returns
+/// A sorted vector of the unique stream offsets found in the index
+///
+pub fn read_multistream_index(index_path: &Path) -> Result<Vec<u64>> {
+    let reader = open_dump(index_path)?;
+    let mut offsets: Vec<u64> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let offset = line
+            .split(':')
+            .next()
+            .and_then(|x| x.parse::<u64>().ok())
+            .ok_or_else(|| Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid multistream index line: '{}'", line)
+            ))?;
+
+        // Lines are grouped by stream so only compare to the last offset seen
+        if offsets.last() != Some(&offset) {
+            offsets.push(offset);
+        }
+    }
+    offsets.sort_unstable();
+    offsets.dedup();
+    return Ok(offsets);
+}
+
+/// Splits a multistream dump into the byte ranges of its independent bzip2 streams.
+///
+/// The ranges cover the whole file: the first range is the stream containing the
+/// `<siteinfo>` header (before the first indexed offset) and the last range runs to the end
+/// of the file (which includes the closing `</mediawiki>` stream).
+///
+/// # Arguments
+/// * `dump_path` - Path to the multistream dump
+/// * `offsets` - Stream offsets, as returned by `read_multistream_index()`
+///
+/// # Returns
+/// A vector of (start, end) byte ranges in file order
+///
+pub fn multistream_ranges(dump_path: &Path, offsets: &[u64]) -> Result<Vec<(u64, u64)>> {
+    let file_size = File::open(dump_path)?.metadata()?.len();
+
+    let mut boundaries: Vec<u64> = Vec::with_capacity(offsets.len() + 2);
+    boundaries.push(0);
+    for offset in offsets.iter() {
+        if *offset > file_size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Multistream index offset {} is past the end of the dump", offset)
+            ));
+        }
+        if *offset != 0 {
+            boundaries.push(*offset);
+        }
+    }
+    boundaries.push(file_size);
+    boundaries.dedup();
+
+    return Ok(boundaries
+        .windows(2)
+        .map(|x| (x[0], x[1]))
+        .collect());
+}
+
+/// Opens a single bzip2 stream from a multistream dump for reading.
+///
+/// # Arguments
+/// * `dump_path` - Path to the multistream dump
+/// * `range` - Byte range of the stream within the dump, see `multistream_ranges()`
+/// * `bytes_read` - Counter the number of compressed bytes read is added to
+///
+pub fn open_stream(dump_path: &Path, range: (u64, u64), bytes_read: Arc<AtomicU64>) -> Result<Box<dyn BufRead>> {
+    let mut file = File::open(dump_path)?;
+    file.seek(SeekFrom::Start(range.0))?;
+    let compressed = BufReader::new(CountingReader::new(file.take(range.1 - range.0), bytes_read));
+    return Ok(Box::new(BufReader::new(MultiBzDecoder::new(compressed))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    }
+
+    fn read_dump(path: &Path) -> String {
+        let mut contents = String::new();
+        open_dump(path).unwrap().read_to_string(&mut contents).unwrap();
+        return contents;
+    }
+
+    #[test]
+    fn detects_compression_from_extension() {
+        assert_eq!(detect_compression(&fixture_path("redirects.xml")).unwrap(), Compression::None);
+        assert_eq!(detect_compression(&fixture_path("redirects.xml.bz2")).unwrap(), Compression::Bzip2);
+        assert_eq!(detect_compression(&fixture_path("redirects.xml.gz")).unwrap(), Compression::Gzip);
+    }
+
+    #[test]
+    fn detects_compression_from_magic_bytes() {
+        for (fixture, compression) in [("redirects.xml.bz2", Compression::Bzip2), ("redirects.xml.gz", Compression::Gzip)].iter() {
+            let path = std::env::temp_dir().join(format!("{}-{}.xml", fixture, std::process::id()));
+            std::fs::copy(fixture_path(fixture), &path).unwrap();
+            let detected = detect_compression(&path).unwrap();
+            let contents = read_dump(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(detected, *compression);
+            assert_eq!(contents, read_dump(&fixture_path("redirects.xml")));
+        }
+    }
+
+    #[test]
+    fn reads_compressed_dumps() {
+        let plain = read_dump(&fixture_path("redirects.xml"));
+        assert!(plain.starts_with("<mediawiki"));
+        assert_eq!(read_dump(&fixture_path("redirects.xml.bz2")), plain);
+        assert_eq!(read_dump(&fixture_path("redirects.xml.gz")), plain);
+    }
+}
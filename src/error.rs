@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// An error encountered while parsing a dump or loading a parsed dataset.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A file could not be opened, read or written
+    Io {
+        /// Path of the file
+        path: String,
+        source: io::Error
+    },
+    /// A page (or other element) of an XML dump is malformed
+    Page {
+        /// Title of the page, if the title had been read before the error
+        title: Option<String>,
+        /// Byte offset of the compressed stream the page is in, for multistream dumps
+        stream: Option<u64>,
+        /// Byte offset into the (decompressed) XML, relative to the start of the stream
+        /// for multistream dumps
+        offset: usize,
+        message: String
+    },
+    /// A line of a dataset or ignore file is malformed
+    Line {
+        /// Path of the file
+        path: String,
+        /// Line number, starting at 1
+        line: usize,
+        message: String
+    },
+    /// A binary dataset is malformed
+    Format {
+        /// Path of the file
+        path: String,
+        message: String
+    }
+}
+
+impl ParseError {
+
+    /// Creates an error for a file that could not be opened or read.
+    pub fn io(path: &str, source: io::Error) -> ParseError {
+        return ParseError::Io { path: path.to_string(), source };
+    }
+
+    /// Creates an error for a malformed line of a file.
+    pub fn line<M: ToString>(path: &str, line: usize, message: M) -> ParseError {
+        return ParseError::Line { path: path.to_string(), line, message: message.to_string() };
+    }
+
+    /// Creates an error for a malformed binary dataset.
+    pub fn format<M: ToString>(path: &str, message: M) -> ParseError {
+        return ParseError::Format { path: path.to_string(), message: message.to_string() };
+    }
+
+    /// Checks if the error only affects a single page, so parsing may continue past it.
+    pub fn is_page_error(&self) -> bool {
+        return matches!(self, ParseError::Page { .. });
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io { path, source } => write!(f, "Could not access '{}': {}", path, source),
+            ParseError::Page { title, stream, offset, message } => {
+                match title {
+                    Some(title) => write!(f, "Malformed page '{}'", title)?,
+                    None => write!(f, "Malformed XML")?
+                }
+                match stream {
+                    Some(stream) => write!(f, " at byte {} of the stream at byte {}", offset, stream)?,
+                    None => write!(f, " at byte {}", offset)?
+                }
+                write!(f, ": {}", message)
+            },
+            ParseError::Line { path, line, message } => write!(f, "{}:{}: {}", path, line, message),
+            ParseError::Format { path, message } => write!(f, "Invalid dataset '{}': {}", path, message)
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match self {
+            ParseError::Io { source, .. } => Some(source),
+            _ => None
+        };
+    }
+}
+
+/// Result type of functions that parse a dump or load a parsed dataset.
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// An error from looking up an article in a parsed dataset or analysing it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalysisError {
+    /// No article or redirect has the name
+    ArticleNotFound(String),
+    /// An article index is not in the dataset
+    ArticleOutOfRange {
+        article: u32,
+        /// Number of articles in the dataset
+        article_count: usize
+    },
+    /// The article is ignored, see `WikipediaAnalysis::ignore_articles()`
+    ArticleIgnored(u32)
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalysisError::ArticleNotFound(name) => write!(f, "Article with name '{}' not found", name),
+            AnalysisError::ArticleOutOfRange { article, article_count } => write!(
+                f, "Article index {} out of range (there are {} articles)", article, article_count),
+            AnalysisError::ArticleIgnored(article) => write!(f, "Article {} is ignored", article)
+        }
+    }
+}
+
+impl Error for AnalysisError {}
+
+/// Result type of functions that look up articles or analyse a parsed dataset.
+pub type AnalysisResult<T> = Result<T, AnalysisError>;
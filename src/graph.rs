@@ -0,0 +1,456 @@
+use memmap2::Mmap;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use crate::binary::{self, Header, Sections};
+use crate::error::{AnalysisError, AnalysisResult, ParseError, ParseResult};
+use crate::metadata::Metadata;
+use crate::parse::{self, Article, Dataset, ParserMode, StringExt};
+
+/// Direction of the links of an article
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Links to the article from other articles
+    Incoming,
+    /// Links from the article to other articles
+    Outgoing
+}
+
+impl Direction {
+    /// Gets the opposite direction
+    pub fn reverse(&self) -> Direction {
+        return match self {
+            Direction::Incoming => Direction::Outgoing,
+            Direction::Outgoing => Direction::Incoming
+        };
+    }
+}
+
+/// Bytes of a dataset in the binary format
+enum GraphData {
+    /// A memory mapped binary dataset
+    Mapped(Mmap),
+    /// A dataset serialized in memory, eg after loading a TSV file.
+    /// Stored as u64 so the sections are aligned the same as in a memory mapped file.
+    Owned(Vec<u64>)
+}
+
+/// Link graph of a parsed dump, used for analysis.
+///
+/// The graph uses the binary dataset format (see `binary::Header`) directly, so a binary dataset
+/// is memory mapped instead of loaded: opening a graph only reads the header, and the pages
+/// of the file are shared in the page cache between processes using the same dataset.
+/// The title table of the dataset is dense: every index from 0 to `len() - 1` has a title, so titles
+/// are looked up by index (`title()`) without a lookup table, and by binary search over the title order
+/// section (`find_title()`), so no HashMap of titles is built. Writing a dataset whose article indices
+/// are not contiguous is an error, see `parse::title_table()`.
+///
+/// Both the incoming and outgoing links of every article are available, see `links()`.
+///
+/// TSV datasets are loaded and converted to the binary format in memory.
+pub struct LinkGraph {
+    data: GraphData,
+    header: Header,
+    sections: Sections,
+    metadata: Metadata
+}
+
+impl LinkGraph {
+
+    /// Opens a dataset, memory mapping it if it is in the binary format.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the dataset, binary or TSV
+    /// * `mode` - Direction of the links in TSV datasets without metadata, which do not record it.
+    ///   TSV datasets only hold one direction, the other direction is computed
+    ///
+    pub fn open(path: &str, mode: ParserMode) -> ParseResult<LinkGraph> {
+        if !binary::is_binary(path)? {
+            let metadata = parse::read_tsv_metadata(path)?.unwrap_or_else(|| Metadata::new(mode));
+            let (article_map, mut articles, redirects_map) = parse::load_from_tsv(&path.to_string())?;
+            return LinkGraph::from_dataset(path, &metadata, &article_map, &mut articles, &redirects_map);
+        }
+
+        if cfg!(target_endian = "big") {
+            return Err(ParseError::format(path, "Binary datasets can only be used on little endian systems"));
+        }
+
+        let file = File::open(path).map_err(|e| ParseError::io(path, e))?;
+        // The mapping is only valid as long as the file is not modified by another process,
+        // datasets are written once by `parse` and then only read
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| ParseError::io(path, e))?;
+        return LinkGraph::new(GraphData::Mapped(mmap), path);
+    }
+
+    /// Creates a graph in memory from the representation used while parsing.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the dataset, used in errors
+    /// * `metadata` - Metadata of the dataset, including the direction of the links in `articles`
+    /// * `article_map` - Hashmap of article name -> article index
+    /// * `articles` - Adjacency list representation of links graph
+    /// * `redirects_map` - Hashmap of redirect name -> article index
+    ///
+    pub fn from_dataset(
+        name: &str,
+        metadata: &Metadata,
+        article_map: &HashMap<String, u32>,
+        articles: &mut [Article],
+        redirects_map: &HashMap<String, u32>) -> ParseResult<LinkGraph> {
+
+        let mut bytes: Vec<u8> = Vec::new();
+        binary::write_binary_to(&mut bytes, name, metadata, article_map, articles, redirects_map)?;
+        let mut words = vec![0u64; bytes.len() / 8];
+        bytemuck::cast_slice_mut::<u64, u8>(&mut words).copy_from_slice(&bytes);
+        return LinkGraph::new(GraphData::Owned(words), name);
+    }
+
+    /// Checks the header, section sizes and metadata of a binary dataset, that its offsets never
+    /// decrease and end at the size of their section and that every article index is in range
+    fn new(data: GraphData, path: &str) -> ParseResult<LinkGraph> {
+        let bytes: &[u8] = match &data {
+            GraphData::Mapped(mmap) => mmap,
+            GraphData::Owned(words) => bytemuck::cast_slice(words)
+        };
+        let header = Header::read(bytes, path)?;
+        let sections = Sections::new(&header, bytes.len() as u64, path)?;
+        let text = std::str::from_utf8(&bytes[sections.metadata.clone()])
+            .map_err(|_| ParseError::format(path, "Invalid UTF-8 in metadata"))?;
+        let metadata = Metadata::from_lines(text.lines().enumerate().map(|(i, x)| (i + 1, x)), path)?;
+        if metadata.mode != header.mode {
+            return Err(ParseError::format(path, "Metadata mode does not match the header"));
+        }
+        metadata.validate(header.article_count, header.link_count, path)?;
+
+        let graph = LinkGraph {
+            data,
+            header,
+            sections,
+            metadata
+        };
+
+        // Everything the accessors index with is checked once here (a single pass over the file),
+        // so a corrupt dataset is an error when opened rather than a panic during analysis
+        let offsets_valid = |offsets: &[u64], size: usize| offsets.first() == Some(&0)
+            && offsets.windows(2).all(|x| x[0] <= x[1])
+            && offsets.last().map(|x| *x as usize == size).unwrap_or(false);
+        if !offsets_valid(graph.offsets(), graph.neighbors().len())
+            || !offsets_valid(graph.reverse_offsets(), graph.reverse_neighbors().len())
+            || !offsets_valid(graph.title_offsets(), graph.sections.titles.len())
+            || !offsets_valid(graph.redirect_title_offsets(), graph.sections.redirect_titles.len()) {
+            return Err(ParseError::format(path, "Invalid offsets"));
+        }
+
+        let article_count = graph.len();
+        let indices_valid = |indices: &[u32]| indices.iter().all(|x| (*x as usize) < article_count);
+        if !indices_valid(graph.neighbors())
+            || !indices_valid(graph.reverse_neighbors())
+            || !indices_valid(graph.title_order())
+            || !indices_valid(graph.redirect_targets()) {
+            return Err(ParseError::format(path, "Article index out of range"));
+        }
+        return Ok(graph);
+    }
+
+    /// All bytes of the dataset
+    fn bytes(&self) -> &[u8] {
+        return match &self.data {
+            GraphData::Mapped(mmap) => mmap,
+            GraphData::Owned(words) => bytemuck::cast_slice(words)
+        };
+    }
+
+    fn offsets(&self) -> &[u64] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.offsets.clone()]);
+    }
+
+    fn neighbors(&self) -> &[u32] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.neighbors.clone()]);
+    }
+
+    fn reverse_offsets(&self) -> &[u64] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.reverse_offsets.clone()]);
+    }
+
+    fn reverse_neighbors(&self) -> &[u32] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.reverse_neighbors.clone()]);
+    }
+
+    fn title_offsets(&self) -> &[u64] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.title_offsets.clone()]);
+    }
+
+    fn redirect_title_offsets(&self) -> &[u64] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.redirect_title_offsets.clone()]);
+    }
+
+    fn redirect_targets(&self) -> &[u32] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.redirect_targets.clone()]);
+    }
+
+    fn title_order(&self) -> &[u32] {
+        return bytemuck::cast_slice(&self.bytes()[self.sections.title_order.clone()]);
+    }
+
+    /// Header of the dataset
+    pub fn header(&self) -> &Header {
+        return &self.header;
+    }
+
+    /// Metadata of the dataset. Datasets written before metadata was recorded only have the mode and counts.
+    pub fn metadata(&self) -> &Metadata {
+        return &self.metadata;
+    }
+
+    /// Number of articles in the graph
+    pub fn len(&self) -> usize {
+        return self.header.article_count as usize;
+    }
+
+    /// Checks if the graph has no articles
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Total number of links in the graph
+    pub fn link_count(&self) -> u64 {
+        return self.header.link_count;
+    }
+
+    /// Gets the links of an article in the direction the dataset was parsed with
+    fn forward_links(&self, article: u32) -> &[u32] {
+        let offsets = self.offsets();
+        let start = offsets[article as usize] as usize;
+        let end = offsets[article as usize + 1] as usize;
+        return &self.neighbors()[start..end];
+    }
+
+    /// Gets the links of an article in the opposite direction to the one the dataset was parsed with
+    fn reverse_links(&self, article: u32) -> &[u32] {
+        let offsets = self.reverse_offsets();
+        let start = offsets[article as usize] as usize;
+        let end = offsets[article as usize + 1] as usize;
+        return &self.reverse_neighbors()[start..end];
+    }
+
+    /// Gets the links to or from an article, sorted by index.
+    ///
+    /// # Arguments
+    /// * `article` - Index of the article
+    /// * `direction` - Whether to get the articles linking to the article (incoming)
+    ///   or the articles it links to (outgoing)
+    ///
+    pub fn links(&self, article: u32, direction: Direction) -> &[u32] {
+        return match self.header.mode.direction() == direction {
+            true => self.forward_links(article),
+            false => self.reverse_links(article)
+        };
+    }
+
+    /// Gets the articles linking to an article, sorted by index
+    pub fn incoming(&self, article: u32) -> &[u32] {
+        return self.links(article, Direction::Incoming);
+    }
+
+    /// Gets the articles an article links to, sorted by index
+    pub fn outgoing(&self, article: u32) -> &[u32] {
+        return self.links(article, Direction::Outgoing);
+    }
+
+    /// Gets the bytes of an article's title
+    fn title_bytes(&self, article: u32) -> &[u8] {
+        let offsets = self.title_offsets();
+        let start = offsets[article as usize] as usize;
+        let end = offsets[article as usize + 1] as usize;
+        return &self.bytes()[self.sections.titles.clone()][start..end];
+    }
+
+    /// Gets the title of an article
+    pub fn title(&self, article: u32) -> &str {
+        return std::str::from_utf8(self.title_bytes(article)).unwrap_or("<invalid UTF-8>");
+    }
+
+    /// Checks an article index is in the graph
+    pub fn check_article(&self, article: u32) -> AnalysisResult<()> {
+        if article as usize >= self.len() {
+            return Err(AnalysisError::ArticleOutOfRange { article, article_count: self.len() });
+        }
+        return Ok(());
+    }
+
+    /// Gets the title of an article, checking the index is in the graph.
+    /// Use `title()` where the index is known to be valid.
+    pub fn title_of(&self, article: u32) -> AnalysisResult<&str> {
+        self.check_article(article)?;
+        return Ok(self.title(article));
+    }
+
+    /// Finds the index of an article by its exact title
+    pub fn find_title(&self, title: &str) -> Option<u32> {
+        let title_order = self.title_order();
+        return match title_order.binary_search_by(|x| self.title_bytes(*x).cmp(title.as_bytes())) {
+            Ok(position) => Some(title_order[position]),
+            Err(_) => None
+        };
+    }
+
+    /// Number of redirects in the graph
+    pub fn redirect_count(&self) -> usize {
+        return self.header.redirect_count as usize;
+    }
+
+    /// Gets the name of a redirect, redirects are sorted by name
+    fn redirect_name_bytes(&self, redirect: usize) -> &[u8] {
+        let offsets = self.redirect_title_offsets();
+        let start = offsets[redirect] as usize;
+        let end = offsets[redirect + 1] as usize;
+        return &self.bytes()[self.sections.redirect_titles.clone()][start..end];
+    }
+
+    /// Finds the index of the article a redirect resolves to by the exact redirect name
+    pub fn find_redirect(&self, name: &str) -> Option<u32> {
+        let mut low = 0;
+        let mut high = self.redirect_count();
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.redirect_name_bytes(middle).cmp(name.as_bytes()) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Some(self.redirect_targets()[middle])
+            }
+        }
+        return None;
+    }
+
+    /// Finds an article by name, following redirects.
+    ///
+    /// The name is looked up as given and, if not found, with the first letter capitalized
+    /// to match wikipedia style titles.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the article or of a redirect to the article
+    ///
+    /// # Returns
+    /// A tuple of (article index, whether the name was a redirect), or None if no article
+    /// or redirect with the name exists.
+    ///
+    pub fn resolve(&self, name: &str) -> Option<(u32, bool)> {
+        let name = name.to_string();
+        for candidate in [name.clone(), name.capitalize_first_letter()].iter() {
+            if let Some(index) = self.find_title(candidate) {
+                return Some((index, false));
+            }
+            if let Some(index) = self.find_redirect(candidate) {
+                return Some((index, true));
+            }
+        }
+        return None;
+    }
+
+    /// Gets the index of an article by name, following redirects, see `resolve()`.
+    pub fn index_of(&self, name: &str) -> AnalysisResult<u32> {
+        return match self.resolve(name) {
+            Some((index, _)) => Ok(index),
+            None => Err(AnalysisError::ArticleNotFound(name.to_string()))
+        };
+    }
+
+    /// Converts the graph to the representation used while parsing, see `parse::load_from_tsv()`.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the dataset, used in errors
+    /// * `mode` - Direction of the links to convert
+    ///
+    pub fn to_dataset(&self, path: &str, mode: ParserMode) -> ParseResult<Dataset> {
+        let mut article_map: HashMap<String, u32> = HashMap::with_capacity(self.len());
+        let mut articles: Vec<Article> = Vec::with_capacity(self.len());
+        for article in 0..self.len() as u32 {
+            let title = std::str::from_utf8(self.title_bytes(article))
+                .map_err(|_| ParseError::format(path, format!("Invalid UTF-8 in title of article {}", article)))?;
+            if article_map.insert(title.to_string(), article).is_some() {
+                return Err(ParseError::format(path, format!("Duplicate title '{}'", title)));
+            }
+
+            let links = self.links(article, mode.direction());
+            if links.iter().any(|x| *x as usize >= self.len()) {
+                return Err(ParseError::format(path, format!("Link out of range in article {}", article)));
+            }
+            articles.push(Article {
+                links: links.to_vec()
+            });
+        }
+
+        let mut redirects_map: HashMap<String, u32> = HashMap::with_capacity(self.redirect_count());
+        for redirect in 0..self.redirect_count() {
+            let name = std::str::from_utf8(self.redirect_name_bytes(redirect))
+                .map_err(|_| ParseError::format(path, format!("Invalid UTF-8 in redirect {}", redirect)))?;
+            let article = self.redirect_targets()[redirect];
+            if article as usize >= self.len() {
+                return Err(ParseError::format(path, format!("Redirect '{}' out of range", name)));
+            }
+            redirects_map.insert(name.to_string(), article);
+        }
+        return Ok((article_map, articles, redirects_map));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the redirects fixture as a binary dataset, corrupts it and opens it
+    fn open_corrupted<F>(name: &str, corrupt: F) -> ParseResult<LinkGraph>
+        where F: FnOnce(&mut [u8], &Sections) {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let (article_map, mut articles, redirects_map) =
+            parse::parse_xml_dump(&fixture, parse::ParseOptions::default()).unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("graph-{}-{}.graph", std::process::id(), name))
+            .to_string_lossy()
+            .to_string();
+        binary::write_binary(
+            &path, &Metadata::new(ParserMode::IncomingLinks), &article_map, &mut articles, &redirects_map
+        ).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let header = Header::read(&bytes, &path).unwrap();
+        let sections = Sections::new(&header, bytes.len() as u64, &path).unwrap();
+        corrupt(&mut bytes, &sections);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let graph = LinkGraph::open(&path, ParserMode::IncomingLinks);
+        std::fs::remove_file(&path).unwrap();
+        return graph;
+    }
+
+    #[test]
+    fn opens_valid_dataset() {
+        let graph = open_corrupted("valid", |_, _| ()).unwrap();
+        assert_eq!(graph.len(), 2);
+    }
+
+    #[test]
+    fn corrupt_interior_offset_is_an_error() {
+        let graph = open_corrupted("offset", |bytes, sections| {
+            // The second of the three offsets, past the end of the neighbors so the third decreases
+            let start = sections.offsets.start + 8;
+            bytes[start..start + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        });
+        match graph {
+            Err(ParseError::Format { message, .. }) => assert_eq!(message, "Invalid offsets"),
+            _ => panic!("Expected a format error")
+        }
+    }
+
+    #[test]
+    fn link_out_of_range_is_an_error() {
+        let graph = open_corrupted("link", |bytes, sections| {
+            let start = sections.neighbors.start;
+            bytes[start..start + 4].copy_from_slice(&100u32.to_le_bytes());
+        });
+        match graph {
+            Err(ParseError::Format { message, .. }) => assert_eq!(message, "Article index out of range"),
+            _ => panic!("Expected a format error")
+        }
+    }
+}
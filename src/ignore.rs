@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::fs::{File, read, read_dir};
+use std::io::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use crate::error::{ParseError, ParseResult};
+use crate::parse::StringExt;
+
+/// How an ignore rule matches titles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleKind {
+    /// Matches one title exactly
+    Exact,
+    /// `glob:` rule, `*` matches any text and `?` matches any single character
+    Glob,
+    /// `re:` rule, a regular expression matched anywhere in the title (use `^` and `$` to anchor it)
+    Regex
+}
+
+impl RuleKind {
+    /// Name of the kind used in reports
+    pub fn name(&self) -> &'static str {
+        return match self {
+            RuleKind::Exact => "exact",
+            RuleKind::Glob => "glob",
+            RuleKind::Regex => "re"
+        };
+    }
+}
+
+/// A rule from an ignore file, see `IgnoreRules`
+#[derive(Debug)]
+pub struct IgnoreRule {
+    pub kind: RuleKind,
+    /// Text of the rule as written in the file, without the `glob:` or `re:` prefix
+    pub text: String,
+    /// Path of the file the rule is from
+    pub path: String,
+    /// Line number of the rule, starting at 1
+    pub line: usize,
+    /// Compiled pattern of glob and regex rules
+    pattern: Option<Regex>,
+    /// Number of titles the rule has matched
+    matches: AtomicU64
+}
+
+impl IgnoreRule {
+    /// Number of titles the rule has matched so far
+    pub fn match_count(&self) -> u64 {
+        return self.matches.load(Ordering::Relaxed);
+    }
+}
+
+/// An ignore file that rules were loaded from, recorded in the metadata of datasets, see `metadata::Metadata`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnoreFile {
+    /// Path of the file
+    pub path: String,
+    /// SHA-256 hash of the file contents, in hex
+    pub sha256: String,
+    /// Number of exact rules in the file
+    pub exact: usize,
+    /// Number of glob rules in the file
+    pub glob: usize,
+    /// Number of regex rules in the file
+    pub regex: usize
+}
+
+/// Rules for the articles to ignore, read from ignore files.
+///
+/// Each line of an ignore file is one rule. Lines starting with `#` are comments and blank lines are skipped.
+/// A rule is either:
+/// * An article title, matched exactly. Underscores are read as spaces and the first letter is
+///   capitalized, so titles copied from wikipedia URLs (eg `January_1900`) match.
+/// * `glob:` followed by a title pattern where `*` matches any text and `?` any single character,
+///   eg `glob:List of * in 1900`. Normalized the same as titles.
+/// * `re:` followed by a regular expression, eg `re:^\d{4} in .*`. Not normalized.
+///
+/// The number of titles each rule matches is counted, see `write_report()`.
+/// Counting is thread safe so titles can be checked while pages are scanned in parallel.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+    /// Files the rules were loaded from
+    files: Vec<IgnoreFile>,
+    /// Maps normalized title => indices of the exact rules for it
+    exact: HashMap<String, Vec<usize>>,
+    /// Indices of the glob and regex rules
+    patterns: Vec<usize>,
+    /// Number of titles matched by any rule
+    matched: AtomicU64
+}
+
+/// Normalizes a title as written in an ignore file: underscores to spaces and the first letter capitalized
+pub fn normalize_title(title: &str) -> String {
+    return title.trim().replace('_', " ").capitalize_first_letter();
+}
+
+/// Converts a glob pattern to an anchored regular expression
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string()))
+        }
+    }
+    pattern.push('$');
+    return pattern;
+}
+
+impl IgnoreRules {
+
+    /// Loads the rules of ignore files.
+    ///
+    /// # Arguments
+    /// * `paths` - Paths of ignore files, or of directories of ignore files
+    ///
+    /// # Errors
+    /// Returns an error if a file cannot be read or a `re:` rule is not a valid regular expression
+    ///
+    pub fn load(paths: &[&str]) -> ParseResult<IgnoreRules> {
+        let mut rules = IgnoreRules::default();
+        for path in paths.iter() {
+            if Path::new(path).is_dir() {
+                // Sort the files so the rules are always reported in the same order
+                let mut file_paths: Vec<String> = Vec::new();
+                for entry in read_dir(path).map_err(|e| ParseError::io(path, e))? {
+                    let file_path = entry.map_err(|e| ParseError::io(path, e))?.path();
+                    file_paths.push(file_path.to_string_lossy().to_string());
+                }
+                file_paths.sort();
+                for file_path in file_paths.iter() {
+                    rules.load_file(file_path)?;
+                }
+            }
+            else {
+                rules.load_file(path)?;
+            }
+        }
+        return Ok(rules);
+    }
+
+    /// Creates rules from the contents of an ignore file that is already in memory.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the rules, used in place of a file path in errors and reports
+    /// * `contents` - Lines of rules in the ignore file format, see `IgnoreRules`
+    ///
+    /// # Errors
+    /// Returns an error if a `re:` rule is not a valid regular expression
+    ///
+    pub fn from_lines(name: &str, contents: &str) -> ParseResult<IgnoreRules> {
+        let mut rules = IgnoreRules::default();
+        rules.add_lines(name, contents)?;
+        return Ok(rules);
+    }
+
+    /// Adds the rules of an ignore file
+    fn load_file(&mut self, path: &str) -> ParseResult<()> {
+        let contents = read(path).map_err(|e| ParseError::io(path, e))?;
+        let contents = std::str::from_utf8(&contents).map_err(|e| {
+            let line = contents[..e.valid_up_to()].iter().filter(|x| **x == b'\n').count() + 1;
+            ParseError::line(path, line, e)
+        })?;
+        return self.add_lines(path, contents);
+    }
+
+    /// Adds the rules in the contents of an ignore file, see `from_lines()`
+    fn add_lines(&mut self, path: &str, contents: &str) -> ParseResult<()> {
+        let first_rule = self.rules.len();
+        for (line_index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, text, pattern) = if let Some(text) = line.strip_prefix("re:") {
+                let pattern = Regex::new(text.trim())
+                    .map_err(|e| ParseError::line(path, line_index + 1, format!("Invalid regex: {}", e)))?;
+                (RuleKind::Regex, text.trim(), Some(pattern))
+            }
+            else if let Some(text) = line.strip_prefix("glob:") {
+                let pattern = Regex::new(&glob_to_regex(&normalize_title(text)))
+                    .map_err(|e| ParseError::line(path, line_index + 1, format!("Invalid glob: {}", e)))?;
+                (RuleKind::Glob, text.trim(), Some(pattern))
+            }
+            else {
+                (RuleKind::Exact, line, None)
+            };
+
+            let index = self.rules.len();
+            match kind {
+                RuleKind::Exact => self.exact.entry(normalize_title(text)).or_default().push(index),
+                _ => self.patterns.push(index)
+            }
+            self.rules.push(IgnoreRule {
+                kind,
+                text: text.to_string(),
+                path: path.to_string(),
+                line: line_index + 1,
+                pattern,
+                matches: AtomicU64::new(0)
+            });
+        }
+
+        let file_rules = &self.rules[first_rule..];
+        let count = |kind: RuleKind| file_rules.iter().filter(|x| x.kind == kind).count();
+        self.files.push(IgnoreFile {
+            path: path.to_string(),
+            sha256: Sha256::digest(contents.as_bytes()).iter().map(|x| format!("{:02x}", x)).collect(),
+            exact: count(RuleKind::Exact),
+            glob: count(RuleKind::Glob),
+            regex: count(RuleKind::Regex)
+        });
+        return Ok(());
+    }
+
+    /// Checks if there are no rules
+    pub fn is_empty(&self) -> bool {
+        return self.rules.len() == 0;
+    }
+
+    /// All rules, in the order they were loaded
+    pub fn rules(&self) -> &[IgnoreRule] {
+        return &self.rules;
+    }
+
+    /// Files the rules were loaded from, in the order they were loaded
+    pub fn files(&self) -> &[IgnoreFile] {
+        return &self.files;
+    }
+
+    /// Number of rules of a kind
+    pub fn count(&self, kind: RuleKind) -> usize {
+        return self.rules.iter().filter(|x| x.kind == kind).count();
+    }
+
+    /// Number of titles matched by any rule so far
+    pub fn matched_count(&self) -> u64 {
+        return self.matched.load(Ordering::Relaxed);
+    }
+
+    /// Checks if a title is ignored, counting the match for every rule that matches it.
+    /// Each title should only be checked once for the counts to be accurate.
+    ///
+    /// # Arguments
+    /// * `title` - Title of the article, as in the dump (with spaces and the first letter capitalized)
+    ///
+    pub fn matches(&self, title: &str) -> bool {
+        let mut is_match = false;
+        if let Some(indices) = self.exact.get(title) {
+            for index in indices.iter() {
+                self.rules[*index].matches.fetch_add(1, Ordering::Relaxed);
+            }
+            is_match = true;
+        }
+        for index in self.patterns.iter() {
+            let rule = &self.rules[*index];
+            match &rule.pattern {
+                Some(pattern) if pattern.is_match(title) => {
+                    rule.matches.fetch_add(1, Ordering::Relaxed);
+                    is_match = true;
+                },
+                _ => ()
+            }
+        }
+        if is_match {
+            self.matched.fetch_add(1, Ordering::Relaxed);
+        }
+        return is_match;
+    }
+
+    /// Gets the exact rules that have not matched any title, eg misspelled titles or titles of
+    /// articles that have been renamed
+    pub fn unmatched_exact(&self) -> Vec<&IgnoreRule> {
+        return self.rules
+            .iter()
+            .filter(|x| x.kind == RuleKind::Exact && x.match_count() == 0)
+            .collect();
+    }
+
+    /// Prints a summary of the rules and their matches to stderr
+    pub fn print_summary(&self) {
+        eprintln!(
+            "Ignore rules ({} exact, {} glob, {} re) matched {} titles, {} exact rules matched nothing",
+            self.count(RuleKind::Exact),
+            self.count(RuleKind::Glob),
+            self.count(RuleKind::Regex),
+            self.matched_count(),
+            self.unmatched_exact().len()
+        );
+    }
+
+    /// Writes a report of the number of titles each rule matched.
+    ///
+    /// The report is a TSV file with a line per rule of the rule's file, line number, kind, text and
+    /// match count, followed by the exact rules that matched nothing.
+    ///
+    /// # Arguments
+    /// * `report_path` - Path to write the report to
+    ///
+    pub fn write_report(&self, report_path: &str) -> ParseResult<()> {
+        let file = File::create(report_path).map_err(|e| ParseError::io(report_path, e))?;
+        let mut writer = BufWriter::new(file);
+        let mut write = || -> Result<()> {
+            writeln!(writer, "file\tline\tkind\trule\tmatches")?;
+            for rule in self.rules.iter() {
+                writeln!(writer, "{}\t{}\t{}\t{}\t{}", rule.path, rule.line, rule.kind.name(), rule.text, rule.match_count())?;
+            }
+            writeln!(writer)?;
+            writeln!(writer, "unmatched exact rules")?;
+            for rule in self.unmatched_exact() {
+                writeln!(writer, "{}\t{}\t{}", rule.path, rule.line, rule.text)?;
+            }
+            return writer.flush();
+        };
+        return write().map_err(|e| ParseError::io(report_path, e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn try_load_rules(contents: &str) -> ParseResult<IgnoreRules> {
+        return IgnoreRules::from_lines("test", contents);
+    }
+
+    fn load_rules(contents: &str) -> IgnoreRules {
+        return try_load_rules(contents).unwrap();
+    }
+
+    #[test]
+    fn normalizes_exact_titles() {
+        let rules = load_rules("# Dates\nJanuary_1900\n\nunited States\n");
+        assert_eq!(rules.rules().len(), 2);
+        assert!(rules.matches("January 1900"));
+        assert!(rules.matches("United States"));
+        assert!(!rules.matches("January_1900"));
+        assert!(!rules.matches("# Dates"));
+    }
+
+    #[test]
+    fn matches_glob_and_regex_rules() {
+        let rules = load_rules("glob:List of *_in 19??\nre:^\\d{4} in .*\n");
+        assert!(rules.matches("List of films in 1950"));
+        assert!(!rules.matches("List of films in 2001"));
+        assert!(rules.matches("1950 in film"));
+        assert!(!rules.matches("Film in 1950"));
+    }
+
+    #[test]
+    fn counts_matches_and_reports_unmatched_entries() {
+        let rules = load_rules("Alpha\nMissing\nre:a$\nalpha\n");
+        assert!(rules.matches("Alpha"));
+        assert!(rules.matches("Beta"));
+        assert!(!rules.matches("Gamma!"));
+
+        let counts: Vec<u64> = rules.rules().iter().map(|x| x.match_count()).collect();
+        assert_eq!(counts, vec![1, 0, 2, 1]);
+        assert_eq!(rules.matched_count(), 2);
+        let unmatched: Vec<&str> = rules.unmatched_exact().iter().map(|x| x.text.as_str()).collect();
+        assert_eq!(unmatched, vec!["Missing"]);
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        match try_load_rules("Alpha\nre:(unclosed\n") {
+            Err(ParseError::Line { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("Expected a line error")
+        }
+    }
+
+    #[test]
+    fn loads_ignore_files() {
+        let path = std::env::temp_dir()
+            .join(format!("ignore-rules-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(&path, "Alpha\nglob:B*\n").unwrap();
+        let rules = IgnoreRules::load(&[&path]).unwrap();
+        assert_eq!(rules.files(), IgnoreRules::from_lines(&path, "Alpha\nglob:B*\n").unwrap().files());
+        assert!(rules.matches("Beta"));
+
+        std::fs::write(&path, b"Alpha\nBad \xff title\n").unwrap();
+        let rules = IgnoreRules::load(&[&path]);
+        std::fs::remove_file(&path).unwrap();
+        match rules {
+            Err(ParseError::Line { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("Expected a line error")
+        }
+    }
+}
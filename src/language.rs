@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::*;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Built in language profiles, as (language code, profile file contents)
+const BUILT_IN_PROFILES: &[(&str, &str)] = &[
+    ("en", include_str!("../languages/en.txt")),
+    ("de", include_str!("../languages/de.txt")),
+    ("fr", include_str!("../languages/fr.txt")),
+    ("es", include_str!("../languages/es.txt")),
+    ("it", include_str!("../languages/it.txt")),
+    ("nl", include_str!("../languages/nl.txt"))
+];
+
+/// Localized keywords used to recognise special pages and templates in a wikipedia dump.
+///
+/// Redirects, disambiguation pages and templates such as "main article" are written using
+/// localized magic words and template names, so a dump of a non-English wikipedia needs a
+/// matching profile to be parsed correctly. See `languages/en.txt` for the file format.
+#[derive(Clone, Debug)]
+pub struct LanguageProfile {
+    /// Magic words a redirect page body starts with, eg "#REDIRECT". Matched case insensitively.
+    pub redirect: Vec<String>,
+    /// Names of templates that mark a page as a disambiguation page.
+    /// A trailing `*` matches any template name starting with the text before it.
+    pub disambiguation_templates: Vec<String>,
+    /// Text in a title that marks a disambiguation page, eg "(disambiguation)"
+    pub disambiguation_titles: Vec<String>,
+    /// Title prefixes of list pages, eg "List of"
+    pub list_prefixes: Vec<String>,
+    /// Names of templates linking to the main article of a topic
+    pub main_article_templates: Vec<String>,
+    /// Names of templates linking to related articles
+    pub see_also_templates: Vec<String>,
+    /// Name prefixes of infobox templates
+    pub infobox_templates: Vec<String>,
+    /// Maps namespace name => namespace key.
+    /// Only used for dumps without a `<siteinfo>` namespace list or `<ns>` elements.
+    pub namespaces: HashMap<String, i32>
+}
+
+impl LanguageProfile {
+
+    /// Gets a built in language profile.
+    ///
+    /// # Arguments
+    /// * `code` - Language code of the wikipedia, eg "en" or "de"
+    ///
+    pub fn built_in(code: &str) -> Option<LanguageProfile> {
+        return BUILT_IN_PROFILES
+            .iter()
+            .find(|(profile_code, _)| *profile_code == code)
+            .map(|(_, contents)| contents
+                .parse::<LanguageProfile>()
+                .expect("Built in language profiles are valid"));
+    }
+
+    /// Language codes of the built in profiles
+    pub fn built_in_codes() -> Vec<&'static str> {
+        return BUILT_IN_PROFILES.iter().map(|(code, _)| *code).collect();
+    }
+
+    /// Loads a language profile, either a built in profile or a profile file.
+    ///
+    /// # Arguments
+    /// * `name` - Language code of a built in profile or the path to a profile file
+    ///
+    pub fn load(name: &str) -> Result<LanguageProfile> {
+        if let Some(profile) = LanguageProfile::built_in(name) {
+            return Ok(profile);
+        }
+        return LanguageProfile::from_file(Path::new(name));
+    }
+
+    /// Loads a language profile from a file.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the profile file
+    ///
+    pub fn from_file(path: &Path) -> Result<LanguageProfile> {
+        return read_to_string(path)?.parse::<LanguageProfile>();
+    }
+
+    /// Checks if a page body is a redirect by looking for a redirect magic word at the start.
+    pub fn is_redirect(&self, body: &str) -> bool {
+        return self.redirect.iter().any(|magic_word| {
+            body.get(..magic_word.len())
+                .map(|start| start.to_lowercase() == magic_word.to_lowercase())
+                .unwrap_or(false)
+        });
+    }
+}
+
+impl FromStr for LanguageProfile {
+    type Err = Error;
+
+    /// Parses the contents of a language profile file.
+    ///
+    /// Each line is of the form `key = value | value | ...`. Namespace names use the key
+    /// `namespace <namespace key>`. Lines starting with `#` are comments.
+    ///
+    /// # Arguments
+    /// * `contents` - Contents of a profile file
+    ///
+    fn from_str(contents: &str) -> Result<LanguageProfile> {
+        let mut profile = LanguageProfile {
+            redirect: Vec::new(),
+            disambiguation_templates: Vec::new(),
+            disambiguation_titles: Vec::new(),
+            list_prefixes: Vec::new(),
+            main_article_templates: Vec::new(),
+            see_also_templates: Vec::new(),
+            infobox_templates: Vec::new(),
+            namespaces: HashMap::new()
+        };
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid_line = |message: &str| Error::new(
+                ErrorKind::InvalidData,
+                format!("Language profile line {}: {}: '{}'", line_number + 1, message, line)
+            );
+
+            let (key, values) = match line.find('=') {
+                Some(position) => (line[..position].trim(), &line[position + 1..]),
+                None => return Err(invalid_line("expected 'key = values'"))
+            };
+            let values: Vec<String> = values
+                .split('|')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect();
+
+            match key {
+                "redirect" => profile.redirect.extend(values),
+                "disambiguation_template" => profile.disambiguation_templates.extend(values),
+                "disambiguation_title" => profile.disambiguation_titles.extend(values),
+                "list_prefix" => profile.list_prefixes.extend(values),
+                "main_article_template" => profile.main_article_templates.extend(values),
+                "see_also_template" => profile.see_also_templates.extend(values),
+                "infobox_template" => profile.infobox_templates.extend(values),
+                _ if key.starts_with("namespace ") => {
+                    let namespace_key = key["namespace ".len()..]
+                        .trim()
+                        .parse::<i32>()
+                        .map_err(|_| invalid_line("invalid namespace key"))?;
+                    for name in values {
+                        profile.namespaces.insert(name, namespace_key);
+                    }
+                },
+                _ => return Err(invalid_line("unknown key"))
+            }
+        }
+
+        if profile.redirect.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "Language profile has no redirect magic words"));
+        }
+        return Ok(profile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_profile() {
+        let contents = "# A test profile\n\
+                        redirect = #WEITERLEITUNG | #REDIRECT\n\
+                        \n\
+                        disambiguation_template = Begriffsklärung*\n\
+                        list_prefix = Liste der | Liste von\n\
+                        namespace 14 = Kategorie\n\
+                        namespace 6 = Datei | Bild\n";
+        let profile = contents.parse::<LanguageProfile>().unwrap();
+        assert_eq!(profile.redirect, vec!["#WEITERLEITUNG", "#REDIRECT"]);
+        assert_eq!(profile.disambiguation_templates, vec!["Begriffsklärung*"]);
+        assert_eq!(profile.list_prefixes, vec!["Liste der", "Liste von"]);
+        assert_eq!(profile.namespaces.get("Kategorie"), Some(&14));
+        assert_eq!(profile.namespaces.get("Datei"), Some(&6));
+        assert_eq!(profile.namespaces.get("Bild"), Some(&6));
+        assert!(profile.see_also_templates.is_empty());
+
+        assert!(profile.is_redirect("#weiterleitung [[Vereinigte Staaten]]"));
+        assert!(profile.is_redirect("#REDIRECT [[Vereinigte Staaten]]"));
+        assert!(!profile.is_redirect("Verweist auf [[Vereinigte Staaten]]"));
+    }
+
+    #[test]
+    fn loads_profile_file() {
+        let path = format!("{}/languages/de.txt", env!("CARGO_MANIFEST_DIR"));
+        let profile = LanguageProfile::load(&path).unwrap();
+        assert_eq!(profile.redirect, LanguageProfile::built_in("de").unwrap().redirect);
+        assert!(LanguageProfile::load("missing-profile.txt").is_err());
+    }
+
+    #[test]
+    fn invalid_profiles_are_an_error() {
+        let invalid = [
+            "redirect = #REDIRECT\nunknown = value",
+            "redirect = #REDIRECT\nno equals sign",
+            "redirect = #REDIRECT\nnamespace x = Talk",
+            "list_prefix = List of"
+        ];
+        for contents in invalid.iter() {
+            match contents.parse::<LanguageProfile>() {
+                Err(e) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+                Ok(_) => panic!("Expected an error parsing '{}'", contents)
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Parses Wikipedia XML dumps into a link graph and analyses it.
+//!
+//! The library is used by the `wikipedia-analysis` CLI and can be used directly:
+//!  * `parse` parses a dump into a dataset (`parse::parse_to_dataset()`) and converts between the
+//!    binary and TSV dataset formats (`parse::convert_dataset()`)
+//!  * `graph::LinkGraph` opens a dataset and looks up the links, titles and redirects of articles
+//!  * `analyze::WikipediaAnalysis` runs the analyses over a graph, optionally ignoring articles
+//!
+//! Functions that read files return a `ParseResult`, functions that take article indices or names
+//! return an `AnalysisResult` rather than panicking on an invalid article.
+//!
+//! ```no_run
+//! use wikipedia_analysis::analyze::WikipediaAnalysis;
+//! use wikipedia_analysis::graph::Direction;
+//! use wikipedia_analysis::parse::ParserMode;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let analysis = WikipediaAnalysis::open("_processed_wikipedia_dump.graph", ParserMode::IncomingLinks)?;
+//! let start = analysis.index_of("Philosophy")?;
+//! let destination = analysis.index_of("United States")?;
+//! if let Some(path) = analysis.get_path_between_articles(start, destination, Direction::Incoming)? {
+//!     let titles: Vec<&str> = path.iter().map(|x| analysis.graph.title(*x)).collect();
+//!     println!("{}", titles.join(" -> "));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod parse;
+pub mod analyze;
+pub mod dump;
+pub mod spill;
+pub mod language;
+pub mod error;
+pub mod progress;
+pub mod binary;
+pub mod graph;
+pub mod ignore;
+pub mod metadata;
+pub mod validate;
+
+pub use crate::error::{AnalysisError, AnalysisResult, ParseError, ParseResult};
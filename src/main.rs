@@ -1,18 +1,19 @@
 
 use std::io;
 use std::fs::File;
-use clap::{Arg, App, SubCommand};
-use rand::{Rng, thread_rng};
-use std::collections::HashMap;
+use clap::{Arg, App, ArgMatches, SubCommand};
+use rand::thread_rng;
+use rand::seq::SliceRandom;
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
-use std::io::{BufReader, BufRead};
-use num_cpus;
+use std::io::{BufReader, BufRead, BufWriter, Write};
 use std::cmp;
 use std::convert::TryInto;
-
-pub mod parse;
-pub mod analyze;
+use std::path::PathBuf;
+use std::process;
+use std::fmt::Display;
+use std::time::Duration;
+use wikipedia_analysis::{analyze, binary, graph, ignore, language, parse, validate};
 
 /// Entry point for CLI parser
 fn main() {
@@ -28,28 +29,132 @@ fn main() {
                 .required(true)
                 .takes_value(true)
                 .index(1)
-                .help("Wikipedia XML dump file to parse")
+                .help("Wikipedia XML dump file to parse (may be compressed as .bz2 or .gz)")
+            )
+            .arg(Arg::with_name("index")
+                .short("x")
+                .long("index")
+                .takes_value(true)
+                .help("Index file of a pages-articles-multistream dump (-multistream-index.txt.bz2). \
+                          If given the streams of the dump are parsed in parallel")
             )
             .arg(Arg::with_name("output")
                 .short("o")
                 .long("output")
                 .takes_value(true)
-                .default_value("_processed_wikipedia_dump.tsv")
-                .help("Output intermediate file")
+                .default_value("_processed_wikipedia_dump.graph")
+                .help("Output intermediate file. Written as TSV if the extension is .tsv, \
+                          otherwise in the binary format")
             )
-            .arg(Arg::with_name("ignore")
+            .arg(Arg::with_name("ignore-dir")
                 .short("n")
                 .long("ignore-dir")
                 .takes_value(true)
                 .help("Path to a directory containing textfiles that \
-                    are a list of article names to ignore")
+                    are a list of article names (or glob: and re: rules) to ignore")
+            )
+            .arg(Arg::with_name("ignore-file")
+                .long("ignore-file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Textfile that is a list of article names (or glob: and re: rules) to ignore \
+                          (supports multiple)")
+            )
+            .arg(Arg::with_name("ignore-report")
+                .long("ignore-report")
+                .takes_value(true)
+                .help("File to write the number of articles each ignore rule matched to")
             )
             .arg(Arg::with_name("reverse")
                 .short("r")
                 .long("reverse")
                 .takes_value(false)
-                .help("Reverse the intermediate file format to be a list of outgoing links \
-                          instead of a list of incoming links")
+                .help("Write a list of outgoing links instead of a list of incoming links to TSV \
+                          intermediate files. Binary intermediate files hold both directions")
+            )
+            .arg(Arg::with_name("num-threads")
+                .short("j")
+                .long("num-threads")
+                .takes_value(true)
+                .required(false)
+                .requires("index")
+                .help("Number of worker threads to use for parsing a multistream dump. Defaults to \
+                          the number of logical CPU cores.")
+            )
+            .arg(Arg::with_name("namespaces")
+                .long("namespaces")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .default_value("0")
+                .help("Keys of the namespaces to include pages from (comma separated). \
+                          Namespace 0 holds articles, see the <siteinfo> block of the dump for others")
+            )
+            .arg(Arg::with_name("language")
+                .short("l")
+                .long("language")
+                .takes_value(true)
+                .default_value("en")
+                .help("Language of the wikipedia the dump is from, used to recognise localized redirects \
+                          and templates. Either a built in profile (en, de, fr, es, it, nl) or the path to \
+                          a language profile file (see languages/en.txt)")
+            )
+            .arg(Arg::with_name("single-pass")
+                .long("single-pass")
+                .takes_value(false)
+                .help("Read the dump only once, holding the links found until all articles are known. \
+                          Links are held in memory unless --spill-file is given")
+            )
+            .arg(Arg::with_name("spill-file")
+                .long("spill-file")
+                .takes_value(true)
+                .requires("single-pass")
+                .help("Temporary file to hold links in during a single pass parse, \
+                          use when the links do not fit in memory")
+            )
+            .arg(Arg::with_name("skip-bad-pages")
+                .long("skip-bad-pages")
+                .takes_value(false)
+                .help("Skip malformed pages instead of stopping, the skipped pages are \
+                          logged and counted")
+            )
+            .arg(Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .takes_value(false)
+                .help("Do not report progress and the parse summary on stderr")
+            )
+            .arg(Arg::with_name("summary-json")
+                .long("summary-json")
+                .takes_value(true)
+                .help("File to write the parse summary (page and link counts, timings) to as JSON")
+            )
+            .arg(Arg::with_name("redirect-report")
+                .long("redirect-report")
+                .takes_value(true)
+                .help("File to write a report of broken and cyclic redirects to")
+            )
+        )
+        .subcommand(SubCommand::with_name("convert")
+            .about("Convert an intermediate file between the TSV and binary formats")
+            .arg(Arg::with_name("input")
+                .required(true)
+                .index(1)
+                .help("Intermediate file to convert (binary or TSV)")
+            )
+            .arg(Arg::with_name("output")
+                .required(true)
+                .index(2)
+                .help("Converted output file. Written as TSV if the extension is .tsv, \
+                          otherwise in the binary format")
+            )
+            .arg(Arg::with_name("reverse")
+                .short("r")
+                .long("reverse")
+                .takes_value(false)
+                .help("TSV intermediate files read or written are lists of outgoing links \
+                          instead of incoming links")
             )
         )
         .subcommand(SubCommand::with_name("analyze")
@@ -58,8 +163,44 @@ fn main() {
                 .short("i")
                 .long("input")
                 .takes_value(true)
-                .default_value("_processed_wikipedia_dump.tsv")
-                .help("Input intermediate file to use for analysis")
+                .default_value("_processed_wikipedia_dump.graph")
+                .help("Input intermediate file to use for analysis (binary or TSV)")
+            )
+            .arg(Arg::with_name("reverse")
+                .short("r")
+                .long("reverse")
+                .takes_value(false)
+                .help("The input is a TSV intermediate file of outgoing links (parsed with --reverse). \
+                          Binary intermediate files hold both directions and TSV files written with \
+                          metadata record their direction")
+            )
+            .arg(Arg::with_name("direction")
+                .long("direction")
+                .takes_value(true)
+                .possible_values(&["incoming", "outgoing"])
+                .default_value("incoming")
+                .help("Direction of the links to analyse, the links to each article (incoming) \
+                          or the links from each article (outgoing)")
+            )
+            .arg(Arg::with_name("ignore-dir")
+                .short("n")
+                .long("ignore-dir")
+                .takes_value(true)
+                .help("Path to a directory containing textfiles that are a list of article names \
+                          (or glob: and re: rules) to leave out of the analysis")
+            )
+            .arg(Arg::with_name("ignore-file")
+                .long("ignore-file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Textfile that is a list of article names (or glob: and re: rules) to leave out \
+                          of the analysis (supports multiple)")
+            )
+            .arg(Arg::with_name("ignore-report")
+                .long("ignore-report")
+                .takes_value(true)
+                .help("File to write the number of articles each ignore rule matched to")
             )
             .arg(Arg::with_name("output")
                 .short("o")
@@ -67,6 +208,19 @@ fn main() {
                 .takes_value(true)
                 .help("Output results file (defaults to STDOUT)")
             )
+            .subcommand(SubCommand::with_name("info")
+                .about("Print the metadata of the intermediate file: the dump it was parsed from, \
+                          the ignore lists used, the article and link counts and the tool version")
+            )
+            .subcommand(SubCommand::with_name("validate")
+                .about("Check the intermediate file for corruption and list every issue found. Exits with \
+                          status 1 if there are errors")
+                .arg(Arg::with_name("strict")
+                    .long("strict")
+                    .takes_value(false)
+                    .help("Also exit with status 1 if there are warnings (eg articles linking to themselves)")
+                )
+            )
             .subcommand(SubCommand::with_name("most-linked")
                 .about("List the files most commonly linked to")
                 .arg(Arg::with_name("count")
@@ -81,7 +235,8 @@ fn main() {
                 .about("List the number of articles with a given number of links")
             )
             .subcommand(SubCommand::with_name("links")
-                .about("Print the names of articles which link to the start article")
+                .about("Print the names of articles which link to (incoming) or are linked from (outgoing) \
+                          the start article")
                 .arg(Arg::with_name("start")
                     .short("s")
                     .long("start")
@@ -109,6 +264,7 @@ fn main() {
                     .index(2)
                     .help("Name of article to find step count to")
                 )
+                .args(&path_constraint_args())
             )
             .subcommand(SubCommand::with_name("steps")
                 .about("Print the articles between two articles, from start to destination")
@@ -128,6 +284,79 @@ fn main() {
                     .index(2)
                     .help("Name of article to find step count to")
                 )
+                .args(&path_constraint_args())
+            )
+            .subcommand(SubCommand::with_name("all-steps")
+                .about("Count all the shortest paths between two articles, list some of them and rank the \
+                          articles they pass through by the number of shortest paths through each")
+                .arg(Arg::with_name("start")
+                    .short("s")
+                    .long("start")
+                    .takes_value(true)
+                    .required(true)
+                    .index(1)
+                    .help("Name of article to start from")
+                )
+                .arg(Arg::with_name("destination")
+                    .short("d")
+                    .long("destination")
+                    .takes_value(true)
+                    .required(true)
+                    .index(2)
+                    .help("Name of article to find the shortest paths to")
+                )
+                .arg(Arg::with_name("paths")
+                    .short("p")
+                    .long("paths")
+                    .takes_value(true)
+                    .default_value("10")
+                    .help("Maximum number of shortest paths to list")
+                )
+                .arg(Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Number of intermediate articles to list. Set to zero to list all of them")
+                )
+            )
+            .subcommand(SubCommand::with_name("k-steps")
+                .about("Print the k shortest paths between two articles that do not go through an article twice, \
+                          from start to destination")
+                .arg(Arg::with_name("start")
+                    .short("s")
+                    .long("start")
+                    .takes_value(true)
+                    .required(true)
+                    .index(1)
+                    .help("Name of article to start from")
+                )
+                .arg(Arg::with_name("destination")
+                    .short("d")
+                    .long("destination")
+                    .takes_value(true)
+                    .required(true)
+                    .index(2)
+                    .help("Name of article to find the paths to")
+                )
+                .arg(Arg::with_name("k")
+                    .short("k")
+                    .long("k")
+                    .takes_value(true)
+                    .default_value("5")
+                    .help("Number of paths to find")
+                )
+                .arg(Arg::with_name("max-expansions")
+                    .long("max-expansions")
+                    .takes_value(true)
+                    .help("Stop searching after going through the links of this many articles, \
+                           printing the paths found so far")
+                )
+                .arg(Arg::with_name("time-limit")
+                    .long("time-limit")
+                    .takes_value(true)
+                    .help("Stop searching after this many seconds, printing the paths found so far")
+                )
             )
             .subcommand(SubCommand::with_name("step-groups")
                 .about("Print the articles grouped by depth away from the root article")
@@ -174,38 +403,105 @@ fn main() {
                           the number of physical CPU cores -1 (or 1 for single core systems).")
                 )
             )
+            .subcommand(SubCommand::with_name("scc")
+                .about("Find the strongly connected components of the link graph, print the distribution \
+                          of their sizes and the number of articles in each region of the bow-tie structure \
+                          around the largest component")
+                .arg(Arg::with_name("articles")
+                    .long("articles")
+                    .takes_value(true)
+                    .help("File to write the component, component size and bow-tie region of each article to")
+                )
+            )
+            .subcommand(SubCommand::with_name("wcc")
+                .about("Find the weakly connected components of the link graph (treating links as undirected), \
+                          print the distribution of their sizes and list the articles in small components")
+                .arg(Arg::with_name("island-size")
+                    .long("island-size")
+                    .takes_value(true)
+                    .default_value("10")
+                    .help("List the articles in components with fewer than this many articles. \
+                              Set to zero to list none")
+                )
+            )
         )
         .get_matches();
 
     if let Some(matches) = matches.subcommand_matches("parse") {
 
-        let to_ignore = match matches.value_of("ignore") {
-            Some(ignore_path) => Some(parse::parse_ignore_directory(&ignore_path.to_string())),
-            None => None
+        let to_ignore = load_ignore_rules(matches);
+
+        let mode = match matches.is_present("reverse") {
+            true => parse::ParserMode::OutgoingLinks,
+            false => parse::ParserMode::IncomingLinks
+        };
+
+        if let Some(thread_count) = matches.value_of("num-threads") {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(thread_count.parse::<usize>().unwrap())
+                .build_global()
+                .unwrap();
+        }
+
+        let pass_mode = match (matches.is_present("single-pass"), matches.value_of("spill-file")) {
+            (true, Some(spill_path)) => parse::PassMode::SinglePassSpill(PathBuf::from(spill_path)),
+            (true, None) => parse::PassMode::SinglePass,
+            (false, _) => parse::PassMode::TwoPass
+        };
+
+        let language_name = matches.value_of("language").unwrap();
+        let language = match language::LanguageProfile::load(language_name) {
+            Ok(language) => language,
+            Err(e) => {
+                println!(
+                    "Could not load language profile '{}' (built in profiles are: {}): {}",
+                    language_name,
+                    language::LanguageProfile::built_in_codes().join(", "),
+                    e
+                );
+                return;
+            }
         };
 
+        let options = parse::ParseOptions {
+            index_path: matches.value_of("index").map(|x| x.to_string()),
+            articles_to_ignore: to_ignore,
+            mode,
+            pass_mode,
+            namespaces: matches
+                .values_of("namespaces")
+                .unwrap()
+                .map(|x| x.trim().parse::<i32>().unwrap_or_else(|e| {
+                    exit_with_error(format!("Invalid namespace key '{}': {}", x, e))
+                }))
+                .collect(),
+            language,
+            redirect_report_path: matches.value_of("redirect-report").map(|x| x.to_string()),
+            ignore_report_path: matches.value_of("ignore-report").map(|x| x.to_string()),
+            skip_bad_pages: matches.is_present("skip-bad-pages"),
+            report_progress: !matches.is_present("quiet"),
+            summary_json_path: matches.value_of("summary-json").map(|x| x.to_string())
+        };
+
+        let input = matches.value_of("input").expect("Input must be given");
+        let output = matches.value_of("output").expect("Output must be given");
+        if let Err(e) = parse::parse_to_dataset(input, output, options) {
+            exit_with_error(e);
+        }
+    }
+
+    else if let Some(matches) = matches.subcommand_matches("convert") {
+        let input = matches.value_of("input").unwrap().to_string();
+        let output = matches.value_of("output").unwrap().to_string();
         let mode = match matches.is_present("reverse") {
             true => parse::ParserMode::OutgoingLinks,
             false => parse::ParserMode::IncomingLinks
         };
 
-        let (mut map, mut articles) = parse::parse_xml_dump(
-            &matches
-                .value_of("input")
-                .expect("Input must be given")
-                .to_string(),
-            to_ignore,
-            mode
-        );
-
-        parse::write_to_tsv(
-            &matches
-            .value_of("output")
-            .expect("Output must be given")
-            .to_string(),
-            &mut map,
-            &mut articles
-        );
+        // TSV datasets with metadata record their direction, which is used instead of --reverse
+        if let Err(e) = parse::convert_dataset(&input, &output, mode) {
+            exit_with_error(e);
+        }
     }
 
     else if let Some(matches) = matches.subcommand_matches("analyze") {
@@ -215,36 +511,88 @@ fn main() {
             None => Box::new(io::stdout())
         };
 
-        let (lookup_table, adjacency_list) = parse::load_from_tsv(
-            &matches
-                .value_of("input")
-                .expect("Input must be given")
-                .to_string(),
-        );
+        let mode = match matches.is_present("reverse") {
+            true => parse::ParserMode::OutgoingLinks,
+            false => parse::ParserMode::IncomingLinks
+        };
+        let direction = match matches.value_of("direction") {
+            Some("outgoing") => graph::Direction::Outgoing,
+            _ => graph::Direction::Incoming
+        };
+
+        let input = matches.value_of("input").expect("Input must be given");
+
+        // Validation checks the dataset without loading it, as loading fails on the first issue
+        if let Some(matches) = matches.subcommand_matches("validate") {
+            let issues = match validate::validate_dataset(input) {
+                Ok(issues) => issues,
+                Err(e) => exit_with_error(e)
+            };
+            for issue in issues.iter() {
+                writeln!(output, "{}", issue).unwrap();
+            }
+            let error_count = issues.iter().filter(|x| x.severity == validate::Severity::Error).count();
+            let warning_count = issues.len() - error_count;
+            writeln!(output, "{} errors, {} warnings", error_count, warning_count).unwrap();
+            output.flush().unwrap();
+
+            if error_count > 0 || (matches.is_present("strict") && warning_count > 0) {
+                process::exit(1);
+            }
+            return;
+        }
 
-        let analysis = analyze::WikipediaAnalysis {
-            article_map: lookup_table,
-            articles: adjacency_list
+        let load_result = graph::LinkGraph::open(input, mode);
+        let graph = match load_result {
+            Ok(graph) => graph,
+            Err(e) => exit_with_error(e)
         };
 
-        let index_map = generate_index_lookup_table(&analysis.article_map);
+        let mut analysis = analyze::WikipediaAnalysis::new(graph);
+        if let Some(to_ignore) = load_ignore_rules(matches) {
+            let ignored_count = analysis.ignore_articles(&to_ignore);
+            eprintln!("Ignoring {} of {} articles", ignored_count, analysis.graph.len());
+            to_ignore.print_summary();
+            if let Some(report_path) = matches.value_of("ignore-report") {
+                if let Err(e) = to_ignore.write_report(report_path) {
+                    exit_with_error(e);
+                }
+            }
+        }
 
-        if let Some(matches) = matches.subcommand_matches("most-linked") {
+        if let Some(_matches) = matches.subcommand_matches("info") {
+            let graph = &analysis.graph;
+            match binary::is_binary(input) {
+                Ok(true) => writeln!(output, "format\tbinary v{}", graph.header().version).unwrap(),
+                Ok(false) => writeln!(output, "format\ttsv").unwrap(),
+                Err(e) => exit_with_error(e)
+            };
+            for line in graph.metadata().to_lines() {
+                writeln!(output, "{}", line).unwrap();
+            }
+            writeln!(output, "redirects\t{}", graph.redirect_count()).unwrap();
+            if !graph.metadata().has_provenance() {
+                eprintln!("The dataset was written before metadata was recorded, parse the dump again to record \
+                           where it came from");
+            }
+        }
+
+        else if let Some(matches) = matches.subcommand_matches("most-linked") {
             let count: u32 = match matches.value_of("count").unwrap().parse().unwrap() {
-                0 => analysis.articles.len().try_into().unwrap(),
+                0 => analysis.article_count().try_into().unwrap(),
                 x => x
             };
 
-            let link_counts = analysis.get_most_links(count);
+            let link_counts = analysis.get_most_links(count, direction);
             writeln!(output, "position\tarticle name\tcount").unwrap();
             for (index, (article_index, count)) in link_counts.iter().enumerate() {
-                let article_name = index_map[*article_index as usize];
+                let article_name = analysis.graph.title(*article_index);
                 writeln!(output, "{}\t{}\t{}", index, article_name, count).unwrap();
             }
         }
 
         else if let Some(_matches) = matches.subcommand_matches("link-histogram") {
-            let link_counts = analysis.get_links_histogram();
+            let link_counts = analysis.get_links_histogram(direction);
             writeln!(output, "link count\tnumber of articles with count").unwrap();
             for (index, count) in link_counts.iter().enumerate() {
                 writeln!(output, "{}\t{}", index, count).unwrap();
@@ -253,16 +601,15 @@ fn main() {
 
         else if let Some(matches) = matches.subcommand_matches("links") {
             let start_article = matches.value_of("start").unwrap();
-            let start_article_index = match analysis.article_map.get(start_article) {
+            let start_article_index = match find_article(&analysis, start_article) {
                 Some(index) => index,
                 None => {
                     println!("Article with name '{}' not found", start_article);
                     return;
                 }
             };
-            let articles = &analysis.articles[*start_article_index as usize].links;
-            for article_index in articles.iter() {
-                writeln!(output, "{}", index_map[*article_index as usize]).unwrap();
+            for article_index in analysis.links(start_article_index, direction) {
+                writeln!(output, "{}", analysis.graph.title(article_index)).unwrap();
             }
         }
 
@@ -270,14 +617,14 @@ fn main() {
 
             let start_article = matches.value_of("start").unwrap();
             let destination_article = matches.value_of("destination").unwrap();
-            let start_article_index = match analysis.article_map.get(start_article) {
+            let start_article_index = match find_article(&analysis, start_article) {
                 Some(index) => index,
                 None => {
                     println!("Article with name '{}' not found", start_article);
                     return;
                 }
             };
-            let destination_article_index = match analysis.article_map.get(destination_article) {
+            let destination_article_index = match find_article(&analysis, destination_article) {
                 Some(index) => index,
                 None => {
                     println!("Article with name '{}' not found", destination_article);
@@ -285,10 +632,11 @@ fn main() {
                 }
             };
 
-            let path = analysis.get_number_of_steps_between_articles(
-                *start_article_index, *destination_article_index
+            let constraints = load_path_constraints(matches, &analysis);
+            let path = analysis.get_constrained_number_of_steps_between_articles(
+                start_article_index, destination_article_index, direction, &constraints
             );
-            match path {
+            match path.unwrap_or_else(|e| exit_with_error(e)) {
                 Some(count) => writeln!(output, "Path: {}", count).unwrap(),
                 None => println!("No path from start to destination found")
             };
@@ -298,14 +646,14 @@ fn main() {
 
             let start_article = matches.value_of("start").unwrap();
             let destination_article = matches.value_of("destination").unwrap();
-            let start_article_index = match analysis.article_map.get(start_article) {
+            let start_article_index = match find_article(&analysis, start_article) {
                 Some(index) => index,
                 None => {
                     println!("Article with name '{}' not found", start_article);
                     return;
                 }
             };
-            let destination_article_index = match analysis.article_map.get(destination_article) {
+            let destination_article_index = match find_article(&analysis, destination_article) {
                 Some(index) => index,
                 None => {
                     println!("Article with name '{}' not found", destination_article);
@@ -313,14 +661,15 @@ fn main() {
                 }
             };
 
-            let step_count = analysis.get_path_between_articles(
-                *start_article_index, *destination_article_index
+            let constraints = load_path_constraints(matches, &analysis);
+            let step_count = analysis.get_constrained_path_between_articles(
+                start_article_index, destination_article_index, direction, &constraints
             );
-            match step_count {
+            match step_count.unwrap_or_else(|e| exit_with_error(e)) {
                 Some(count) => {
                     let article_names: Vec<String> = count
                         .iter()
-                        .map(|x| index_map[*x as usize].clone())
+                        .map(|x| analysis.graph.title(*x).to_string())
                         .collect();
                     writeln!(output, "Step count: {}", article_names.join(",")).unwrap();
                 },
@@ -330,6 +679,118 @@ fn main() {
             };
         }
 
+        else if let Some(matches) = matches.subcommand_matches("all-steps") {
+
+            let start_article = matches.value_of("start").unwrap();
+            let destination_article = matches.value_of("destination").unwrap();
+            let start_article_index = match find_article(&analysis, start_article) {
+                Some(index) => index,
+                None => {
+                    println!("Article with name '{}' not found", start_article);
+                    return;
+                }
+            };
+            let destination_article_index = match find_article(&analysis, destination_article) {
+                Some(index) => index,
+                None => {
+                    println!("Article with name '{}' not found", destination_article);
+                    return;
+                }
+            };
+            let max_paths: usize = matches.value_of("paths").unwrap().parse().unwrap();
+            let count: usize = matches.value_of("count").unwrap().parse().unwrap();
+
+            let all_paths = analysis.get_all_paths_between_articles(
+                start_article_index, destination_article_index, direction, max_paths
+            );
+            let all_paths = match all_paths.unwrap_or_else(|e| exit_with_error(e)) {
+                Some(all_paths) => all_paths,
+                None => {
+                    writeln!(output, "No path from start to destination found").unwrap();
+                    return;
+                }
+            };
+
+            writeln!(output, "Step count: {}", all_paths.steps).unwrap();
+            match all_paths.count {
+                u64::MAX => writeln!(output, "Shortest paths: at least {}", all_paths.count).unwrap(),
+                _ => writeln!(output, "Shortest paths: {}", all_paths.count).unwrap()
+            };
+            for path in all_paths.paths.iter() {
+                let article_names: Vec<&str> = path
+                    .iter()
+                    .map(|x| analysis.graph.title(*x))
+                    .collect();
+                writeln!(output, "Path: {}", article_names.join(",")).unwrap();
+            }
+
+            writeln!(output, "position\tarticle name\tshortest paths through\tshare of shortest paths").unwrap();
+            let count = match count {
+                0 => all_paths.through.len(),
+                x => x
+            };
+            for (index, (article_index, paths_through)) in all_paths.through.iter().take(count).enumerate() {
+                let share = *paths_through as f64 / all_paths.count as f64;
+                writeln!(output, "{}\t{}\t{}\t{:.4}", index, analysis.graph.title(*article_index), paths_through, share)
+                    .unwrap();
+            }
+        }
+
+        else if let Some(matches) = matches.subcommand_matches("k-steps") {
+
+            let start_article = matches.value_of("start").unwrap();
+            let destination_article = matches.value_of("destination").unwrap();
+            let start_article_index = match find_article(&analysis, start_article) {
+                Some(index) => index,
+                None => {
+                    println!("Article with name '{}' not found", start_article);
+                    return;
+                }
+            };
+            let destination_article_index = match find_article(&analysis, destination_article) {
+                Some(index) => index,
+                None => {
+                    println!("Article with name '{}' not found", destination_article);
+                    return;
+                }
+            };
+            let k: usize = matches.value_of("k").unwrap().parse().unwrap();
+            let budget = analyze::SearchBudget {
+                max_expansions: matches.value_of("max-expansions").map(|x| match x.parse() {
+                    Ok(max_expansions) => max_expansions,
+                    Err(e) => exit_with_error(format!("Invalid maximum number of expansions '{}': {}", x, e))
+                }),
+                time_limit: matches.value_of("time-limit").map(|x| {
+                    let seconds: f64 = match x.parse() {
+                        Ok(seconds) => seconds,
+                        Err(e) => exit_with_error(format!("Invalid time limit '{}': {}", x, e))
+                    };
+                    // Negative, infinite and NaN limits are errors
+                    return match Duration::try_from_secs_f64(seconds) {
+                        Ok(time_limit) => time_limit,
+                        Err(e) => exit_with_error(format!("Invalid time limit '{}': {}", x, e))
+                    };
+                })
+            };
+
+            let k_paths = analysis.get_k_shortest_paths_between_articles(
+                start_article_index, destination_article_index, direction, k, budget
+            ).unwrap_or_else(|e| exit_with_error(e));
+            if k_paths.paths.len() == 0 {
+                writeln!(output, "No path from start to destination found").unwrap();
+            }
+            for path in k_paths.paths.iter() {
+                let article_names: Vec<&str> = path
+                    .iter()
+                    .map(|x| analysis.graph.title(*x))
+                    .collect();
+                writeln!(output, "{}\t{}", path.len() - 1, article_names.join(",")).unwrap();
+            }
+            if k_paths.exhausted {
+                eprintln!("Search budget ran out after {} of {} paths", k_paths.paths.len(), k);
+            }
+        }
+
         else if let Some(matches) = matches.subcommand_matches("step-groups") {
             let depth = match matches.value_of("depth") {
                 Some(match_value) => Some(match_value.parse().unwrap()),
@@ -343,24 +804,26 @@ fn main() {
             let mut roots: Vec<u32> = Vec::new();
             if matches.is_present("use-most-linked") {
                 let count: u32 = matches.value_of("use-most-linked").unwrap().parse().unwrap();
-                roots = analysis.get_most_links(count)
+                roots = analysis.get_most_links(count, direction)
                     .iter()
                     .map(|x| x.0)
                     .collect();
             }
             else if matches.is_present("use-random") {
                 let count: u32 = matches.value_of("use-random").unwrap().parse().unwrap();
+                let articles: Vec<u32> = analysis.articles().collect();
                 let mut rng = thread_rng();
                 for _ in 0..count {
-                    let article_index = rng.gen_range(0, analysis.articles.len());
-                    roots.push(article_index.try_into().unwrap());
+                    if let Some(article_index) = articles.choose(&mut rng) {
+                        roots.push(*article_index);
+                    }
                 }
             }
             else if matches.is_present("roots") {
                 for article in matches.values_of("roots").unwrap() {
-                    match analysis.article_map.get(article) {
+                    match find_article(&analysis, article) {
                         Some(article_index) => {
-                            roots.push(*article_index);
+                            roots.push(article_index);
                         },
                         None => {
                             println!("Article with name '{}' not found", article);
@@ -374,9 +837,9 @@ fn main() {
                 let reader = BufReader::new(file);
                 for line in reader.lines() {
                     let article = line.unwrap();
-                    match analysis.article_map.get(&article) {
+                    match find_article(&analysis, &article) {
                         Some(article_index) => {
-                            roots.push(*article_index);
+                            roots.push(article_index);
                         },
                         None => {
                             println!("Article with name '{}' not found", article);
@@ -392,14 +855,14 @@ fn main() {
             let write_mutex = Arc::new(Mutex::new(output));
 
             let steps_function = |root_article_index| {
-                let step_groups = analysis.get_step_count_groups(
-                    root_article_index, depth
-                );
+                let step_groups = analysis
+                    .get_step_count_groups(root_article_index, depth, direction)
+                    .unwrap_or_else(|e| exit_with_error(e));
                 let steps_strs: Vec<String> = step_groups
                     .iter()
                     .map(|x| x.len().to_string())
                     .collect();
-                let root_article_name = index_map[root_article_index as usize];
+                let root_article_name = analysis.graph.title(root_article_index);
 
                 let mut mutex = write_mutex.lock().unwrap();
                 writeln!(mutex, "{}\t{}", root_article_name, steps_strs.join("\t")).unwrap();
@@ -414,21 +877,176 @@ fn main() {
 
             roots.into_par_iter().for_each(steps_function);
         }
+
+        else if let Some(matches) = matches.subcommand_matches("scc") {
+
+            let components = analysis.get_strongly_connected_components();
+            let regions = analysis.get_bow_tie(&components);
+            let article_count = analysis.article_count();
+
+            writeln!(output, "Components: {}", components.sizes.len()).unwrap();
+            if let Some(giant) = components.largest() {
+                let size = components.sizes[giant as usize];
+                writeln!(output, "Largest component: {} articles ({:.2}%)",
+                    size, 100.0 * size as f64 / article_count as f64).unwrap();
+            }
+
+            writeln!(output, "component size\tcomponents").unwrap();
+            for (size, count) in components.size_distribution() {
+                writeln!(output, "{}\t{}", size, count).unwrap();
+            }
+
+            writeln!(output, "region\tarticles\tshare of articles").unwrap();
+            for region in analyze::BowTieRegion::ALL.iter() {
+                let count = regions.iter().filter(|x| **x == Some(*region)).count();
+                writeln!(output, "{}\t{}\t{:.4}", region.name(), count, count as f64 / article_count as f64).unwrap();
+            }
+
+            if let Some(articles_path) = matches.value_of("articles") {
+                let file = File::create(articles_path).unwrap_or_else(|e| exit_with_error(e));
+                let mut file = BufWriter::new(file);
+                writeln!(file, "article name\tcomponent\tcomponent size\tregion").unwrap();
+                for article in analysis.articles() {
+                    let component = components.component_of[article as usize];
+                    writeln!(
+                        file, "{}\t{}\t{}\t{}",
+                        analysis.graph.title(article),
+                        component,
+                        components.sizes[component as usize],
+                        regions[article as usize].unwrap().name()).unwrap();
+                }
+            }
+        }
+
+        else if let Some(matches) = matches.subcommand_matches("wcc") {
+
+            let island_size: u32 = matches.value_of("island-size").unwrap().parse().unwrap();
+            let components = analysis.get_weakly_connected_components();
+            let article_count = analysis.article_count();
+
+            writeln!(output, "Components: {}", components.sizes.len()).unwrap();
+            if let Some(largest) = components.largest() {
+                let size = components.sizes[largest as usize];
+                writeln!(output, "Largest component: {} articles ({:.2}%)",
+                    size, 100.0 * size as f64 / article_count as f64).unwrap();
+            }
+
+            writeln!(output, "component size\tcomponents").unwrap();
+            for (size, count) in components.size_distribution() {
+                writeln!(output, "{}\t{}", size, count).unwrap();
+            }
+
+            // Articles of each island in index order, grouped by component
+            let mut islands: Vec<u32> = analysis
+                .articles()
+                .filter(|x| components.sizes[components.component_of[*x as usize] as usize] < island_size)
+                .collect();
+            islands.sort_by_key(|x| components.component_of[*x as usize]);
+            let island_count = components.sizes.iter().filter(|x| **x < island_size).count();
+            writeln!(output, "Islands of fewer than {} articles: {} articles in {} components",
+                island_size, islands.len(), island_count).unwrap();
+            if islands.len() > 0 {
+                writeln!(output, "component\tcomponent size\tarticle name").unwrap();
+            }
+            for article in islands {
+                let component = components.component_of[article as usize];
+                writeln!(output, "{}\t{}\t{}",
+                    component, components.sizes[component as usize], analysis.graph.title(article)).unwrap();
+            }
+        }
     }
     else {
         print!("{}", matches.usage());
     }
 }
 
-/// Generates a hashmap from article index -> article name
-fn generate_index_lookup_table(article_map: &HashMap<String, u32>) -> Vec<&String> {
-    unsafe {
-        let mut index_lookup_table = Vec::with_capacity(article_map.len());
-        index_lookup_table.set_len(article_map.len());
-        for (article_name, index) in article_map.iter() {
-            index_lookup_table[*index as usize] = article_name;
-        }
-        return index_lookup_table;
+/// Prints an error and exits with a failure exit code.
+fn exit_with_error<E: Display>(error: E) -> ! {
+    eprintln!("Error: {}", error);
+    process::exit(1);
+}
+
+/// Loads the ignore rules given with `--ignore-dir` and `--ignore-file`, if any.
+fn load_ignore_rules(matches: &ArgMatches) -> Option<ignore::IgnoreRules> {
+    let mut paths: Vec<&str> = Vec::new();
+    paths.extend(matches.value_of("ignore-dir"));
+    paths.extend(matches.values_of("ignore-file").into_iter().flatten());
+    if paths.len() == 0 {
+        return None;
     }
+    return match ignore::IgnoreRules::load(&paths) {
+        Ok(rules) => Some(rules),
+        Err(e) => exit_with_error(e)
+    };
+}
 
+/// Arguments of the constraints on the paths of `steps` and `count-steps`, see `load_path_constraints()`.
+fn path_constraint_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    return vec![
+        Arg::with_name("avoid")
+            .long("avoid")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Name of an article the path may not go through (supports multiple)"),
+        Arg::with_name("avoid-file")
+            .long("avoid-file")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Textfile that is a list of article names (or glob: and re: rules) the path may not \
+                      go through (supports multiple)"),
+        Arg::with_name("via")
+            .long("via")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Name of an article the path must go through. Multiple are gone through in the order given"),
+        Arg::with_name("max-degree")
+            .long("max-degree")
+            .takes_value(true)
+            .help("Leave out articles with more than this many links (incoming and outgoing) \
+                      part way along the path")
+    ];
+}
+
+/// Loads the path constraints given with `--avoid`, `--avoid-file`, `--via` and `--max-degree`.
+/// Exits with an error if an article is not found or the maximum degree is invalid.
+fn load_path_constraints(matches: &ArgMatches, analysis: &analyze::WikipediaAnalysis) -> analyze::PathConstraints {
+    let index_of = |name: &str| match find_article(analysis, name) {
+        Some(article_index) => article_index,
+        None => exit_with_error(format!("Article with name '{}' not found", name))
+    };
+    let mut constraints = analyze::PathConstraints {
+        avoid: matches.values_of("avoid").into_iter().flatten().map(index_of).collect(),
+        via: matches.values_of("via").into_iter().flatten().map(index_of).collect(),
+        max_degree: matches.value_of("max-degree").map(|x| match x.parse() {
+            Ok(max_degree) => max_degree,
+            Err(e) => exit_with_error(format!("Invalid maximum degree '{}': {}", x, e))
+        })
+    };
+
+    let avoid_files: Vec<&str> = matches.values_of("avoid-file").into_iter().flatten().collect();
+    if avoid_files.len() > 0 {
+        let rules = ignore::IgnoreRules::load(&avoid_files).unwrap_or_else(|e| exit_with_error(e));
+        constraints.avoid.extend(analysis.articles().filter(|x| rules.matches(analysis.graph.title(*x))));
+    }
+    return constraints;
+}
+
+/// Finds an article by name, following redirects.
+/// When the name is a redirect the canonical article name is reported on stderr.
+fn find_article(
+    analysis: &analyze::WikipediaAnalysis,
+    name: &str) -> Option<u32> {
+
+    return match analysis.find_article(name) {
+        Some((article_index, is_redirect)) => {
+            if is_redirect {
+                eprintln!("'{}' redirects to '{}'", name, analysis.graph.title(article_index));
+            }
+            Some(article_index)
+        },
+        None => None
+    };
 }
@@ -0,0 +1,275 @@
+use std::path::Path;
+use regex::Regex;
+use crate::error::{ParseError, ParseResult};
+use crate::ignore::{IgnoreFile, IgnoreRules};
+use crate::parse::{self, ParserMode};
+
+/// Version of this tool, recorded in the datasets it writes
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Provenance of a parsed dataset: which dump, settings and tool version produced it.
+///
+/// Metadata is stored as lines of `key \t value` in both dataset formats, at the start of TSV datasets
+/// (each line prefixed with `#`) and in the metadata section of binary datasets. The article and link
+/// counts are checked against the dataset when it is loaded. Datasets written before metadata was
+/// recorded only have the direction and counts, which come from the dataset itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    /// Version of the tool that wrote the dataset
+    pub tool_version: Option<String>,
+    /// File name of the dump the dataset was parsed from
+    pub source_dump: Option<String>,
+    /// Size of the dump file in bytes
+    pub source_size: Option<u64>,
+    /// Date of the dump as YYYY-MM-DD, see `dump_date_from_file_name()`.
+    /// `<siteinfo>` does not include a date so this is taken from the dump file name, eg enwiki-20171103-...
+    pub dump_date: Option<String>,
+    /// Database name of the wiki from the `<siteinfo>` of the dump, eg enwiki
+    pub site: Option<String>,
+    /// Direction of the links in the dataset. Binary datasets hold both directions, this is the
+    /// direction of the link sections (see `binary::Header`)
+    pub mode: ParserMode,
+    /// Ignore files of the articles ignored while parsing
+    pub ignore_files: Vec<IgnoreFile>,
+    /// Number of articles in the dataset
+    pub article_count: u64,
+    /// Number of links in the dataset, after removing duplicates
+    pub link_count: u64
+}
+
+impl Metadata {
+
+    /// Creates metadata with no provenance, for datasets written before metadata was recorded.
+    pub fn new(mode: ParserMode) -> Metadata {
+        return Metadata {
+            tool_version: None,
+            source_dump: None,
+            source_size: None,
+            dump_date: None,
+            site: None,
+            mode,
+            ignore_files: Vec::new(),
+            article_count: 0,
+            link_count: 0
+        };
+    }
+
+    /// Creates the metadata of a dataset parsed from a dump.
+    /// The counts are filled in when the dataset is written.
+    ///
+    /// # Arguments
+    /// * `xml_path` - Path to the dump
+    /// * `mode` - Direction of the links the dump is parsed with
+    /// * `to_ignore` - Rules of the articles ignored while parsing
+    ///
+    /// # Errors
+    /// Returns an error if the dump cannot be read
+    ///
+    pub fn for_dump(xml_path: &str, mode: ParserMode, to_ignore: Option<&IgnoreRules>) -> ParseResult<Metadata> {
+        let path = Path::new(xml_path);
+        let source_size = std::fs::metadata(path).map_err(|e| ParseError::io(xml_path, e))?.len();
+        let source_dump = path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|| xml_path.to_string());
+
+        return Ok(Metadata {
+            tool_version: Some(TOOL_VERSION.to_string()),
+            dump_date: dump_date_from_file_name(&source_dump),
+            source_dump: Some(source_dump),
+            source_size: Some(source_size),
+            site: parse::read_site_name(xml_path)?,
+            mode,
+            ignore_files: to_ignore.map(|x| x.files().to_vec()).unwrap_or_default(),
+            article_count: 0,
+            link_count: 0
+        });
+    }
+
+    /// Checks if the metadata records where the dataset came from, ie it was not written before metadata was recorded
+    pub fn has_provenance(&self) -> bool {
+        return self.source_dump.is_some();
+    }
+
+    /// Formats the metadata as lines of `key \t value`, see `from_lines()`.
+    /// Values that are not known are left out.
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut optional = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                lines.push(format!("{}\t{}", key, value));
+            }
+        };
+        optional("tool_version", self.tool_version.clone());
+        optional("source_dump", self.source_dump.clone());
+        optional("source_size", self.source_size.map(|x| x.to_string()));
+        optional("dump_date", self.dump_date.clone());
+        optional("site", self.site.clone());
+
+        lines.push(format!("mode\t{}", match self.mode {
+            ParserMode::IncomingLinks => "incoming",
+            ParserMode::OutgoingLinks => "outgoing"
+        }));
+        for file in self.ignore_files.iter() {
+            lines.push(format!(
+                "ignore\t{}\t{}\t{}\t{}\t{}", file.path, file.sha256, file.exact, file.glob, file.regex));
+        }
+        lines.push(format!("articles\t{}", self.article_count));
+        lines.push(format!("links\t{}", self.link_count));
+        return lines;
+    }
+
+    /// Parses metadata from lines of `key \t value`, see `to_lines()`.
+    /// Unknown keys are skipped so metadata written by newer versions can be read.
+    ///
+    /// # Arguments
+    /// * `lines` - Tuples of (line number, line)
+    /// * `path` - Path of the dataset, used in errors
+    ///
+    /// # Errors
+    /// Returns an error if a value is invalid or the mode or counts are missing
+    ///
+    pub fn from_lines<'a, I>(lines: I, path: &str) -> ParseResult<Metadata>
+        where I: Iterator<Item = (usize, &'a str)> {
+
+        let mut metadata = Metadata::new(ParserMode::IncomingLinks);
+        let mut mode: Option<ParserMode> = None;
+        let mut article_count: Option<u64> = None;
+        let mut link_count: Option<u64> = None;
+
+        for (line_number, line) in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let invalid = |message: &str| ParseError::line(path, line_number, format!("{} in metadata '{}'", message, line));
+            let value = match fields.get(1) {
+                Some(value) => value.to_string(),
+                None => return Err(invalid("Missing value"))
+            };
+            let number = |value: &str| value.parse::<u64>().map_err(|_| invalid("Invalid number"));
+
+            match fields[0] {
+                "tool_version" => metadata.tool_version = Some(value),
+                "source_dump" => metadata.source_dump = Some(value),
+                "source_size" => metadata.source_size = Some(number(&value)?),
+                "dump_date" => metadata.dump_date = Some(value),
+                "site" => metadata.site = Some(value),
+                "mode" => mode = match value.as_str() {
+                    "incoming" => Some(ParserMode::IncomingLinks),
+                    "outgoing" => Some(ParserMode::OutgoingLinks),
+                    _ => return Err(invalid("Invalid mode"))
+                },
+                "ignore" => {
+                    if fields.len() != 6 {
+                        return Err(invalid("Expected path, hash and 3 rule counts"));
+                    }
+                    metadata.ignore_files.push(IgnoreFile {
+                        path: value,
+                        sha256: fields[2].to_string(),
+                        exact: number(fields[3])? as usize,
+                        glob: number(fields[4])? as usize,
+                        regex: number(fields[5])? as usize
+                    });
+                },
+                "articles" => article_count = Some(number(&value)?),
+                "links" => link_count = Some(number(&value)?),
+                _ => ()
+            }
+        }
+
+        match (mode, article_count, link_count) {
+            (Some(mode), Some(article_count), Some(link_count)) => {
+                metadata.mode = mode;
+                metadata.article_count = article_count;
+                metadata.link_count = link_count;
+            },
+            _ => return Err(ParseError::format(path, "Metadata is missing the mode, article count or link count"))
+        }
+        return Ok(metadata);
+    }
+
+    /// Checks the counts of the metadata match a dataset.
+    ///
+    /// # Arguments
+    /// * `article_count` - Number of articles in the dataset
+    /// * `link_count` - Number of links in the dataset
+    /// * `path` - Path of the dataset, used in errors
+    ///
+    pub fn validate(&self, article_count: u64, link_count: u64, path: &str) -> ParseResult<()> {
+        if self.article_count != article_count {
+            return Err(ParseError::format(path, format!(
+                "Metadata records {} articles but the dataset has {}", self.article_count, article_count)));
+        }
+        if self.link_count != link_count {
+            return Err(ParseError::format(path, format!(
+                "Metadata records {} links but the dataset has {}", self.link_count, link_count)));
+        }
+        return Ok(());
+    }
+}
+
+/// Gets the date of a dump from its file name, dumps are named with the date they were started on,
+/// eg `enwiki-20240101-pages-articles-multistream.xml.bz2`.
+///
+/// # Returns
+/// The date as YYYY-MM-DD, or None if the name has no 8 digit date with a valid month and day
+/// (eg `enwiki-latest-pages-articles.xml.bz2`)
+///
+pub fn dump_date_from_file_name(file_name: &str) -> Option<String> {
+    let date_regex = Regex::new(r"(?:^|\D)(\d{4})(0[1-9]|1[0-2])(0[1-9]|[12]\d|3[01])(?:\D|$)").unwrap();
+    return date_regex
+        .captures(file_name)
+        .map(|x| format!("{}-{}-{}", &x[1], &x[2], &x[3]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_lines() {
+        let metadata = Metadata {
+            tool_version: Some(TOOL_VERSION.to_string()),
+            source_dump: Some(String::from("enwiki-20171103-pages-articles.xml.bz2")),
+            source_size: Some(1234),
+            dump_date: Some(String::from("2017-11-03")),
+            site: Some(String::from("enwiki")),
+            mode: ParserMode::OutgoingLinks,
+            ignore_files: vec![IgnoreFile {
+                path: String::from("ignored-articles/dates.txt"),
+                sha256: String::from("00ff"),
+                exact: 3,
+                glob: 2,
+                regex: 1
+            }],
+            article_count: 10,
+            link_count: 20
+        };
+        let lines = metadata.to_lines();
+        let parsed = Metadata::from_lines(lines.iter().enumerate().map(|(i, x)| (i + 1, x.as_str())), "test").unwrap();
+        assert_eq!(parsed, metadata);
+    }
+
+    #[test]
+    fn requires_mode_and_counts() {
+        let lines = ["tool_version\t0.1.0", "mode\tincoming", "articles\t3"];
+        assert!(Metadata::from_lines(lines.iter().enumerate().map(|(i, x)| (i + 1, *x)), "test").is_err());
+    }
+
+    #[test]
+    fn reads_dump_date_from_file_name() {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let metadata = Metadata::for_dump(&fixture, ParserMode::IncomingLinks, None).unwrap();
+        assert_eq!(metadata.source_dump.as_deref(), Some("redirects.xml"));
+        assert_eq!(metadata.dump_date, None);
+        assert_eq!(metadata.site.as_deref(), Some("testwiki"));
+
+        assert_eq!(
+            dump_date_from_file_name("enwiki-20240101-pages-articles-multistream.xml.bz2").as_deref(),
+            Some("2024-01-01"));
+        assert_eq!(dump_date_from_file_name("20171103.xml").as_deref(), Some("2017-11-03"));
+        assert_eq!(dump_date_from_file_name("enwiki-latest-pages-articles.xml.bz2"), None);
+        // Invalid month and day, and too many digits
+        assert_eq!(dump_date_from_file_name("enwiki-20241301-pages-articles.xml"), None);
+        assert_eq!(dump_date_from_file_name("enwiki-20240132-pages-articles.xml"), None);
+        assert_eq!(dump_date_from_file_name("enwiki-202401011-pages-articles.xml"), None);
+    }
+}
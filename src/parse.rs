@@ -1,23 +1,301 @@
 use quick_xml::Reader;
-use quick_xml::events::Event;
-use std::fs::{File, read_dir};
+use quick_xml::events::{Event, BytesStart};
+use std::fs::{File, write};
 use std::io::*;
 use std::collections::{HashMap, HashSet};
 use regex::Regex;
 use std::convert::TryInto;
+use std::mem;
+use std::path::{Path, PathBuf};
+use crate::dump;
+use crate::binary;
+use crate::graph::{Direction, LinkGraph};
+use crate::spill::LinkStore;
+use crate::progress::{Progress, ParseStats};
+use crate::language::LanguageProfile;
+use crate::ignore::IgnoreRules;
+use crate::metadata::Metadata;
+pub use crate::error::{ParseError, ParseResult};
+use rayon::prelude::*;
 
 // XML parsing state
 enum ParserState {
     Idle,
     ReadingTitle,
+    ReadingNamespace,
+    /// Reading the name of the namespace with the contained key in the siteinfo block
+    ReadingNamespaceName(i32),
     ReadingBody
 }
 
+/// A valid page read from the dump
+struct Page {
+    title: String,
+    /// Target of the `<redirect title="...">` element if the page has one
+    redirect: Option<String>,
+    body: String
+}
+
+/// Decides which pages of a dump are valid, see `scan_pages()`
+struct PageFilter<'a> {
+    /// Namespace keys of pages to accept
+    namespaces: &'a HashSet<i32>,
+    language: &'a LanguageProfile,
+    /// Skip malformed pages instead of stopping with an error
+    skip_bad_pages: bool,
+    /// Progress of the pages read
+    progress: &'a Progress
+}
+
+/// Information from the `<siteinfo>` header of a dump
+#[derive(Clone)]
+struct SiteInfo {
+    /// Maps namespace name => namespace key, eg "Category" => 14.
+    /// The main (article) namespace has an empty name and key 0.
+    namespaces: HashMap<String, i32>
+}
+
+impl SiteInfo {
+
+    /// Creates site information with the namespace names of a language profile,
+    /// these are used until the `<siteinfo>` block of the dump is read.
+    fn from_language(language: &LanguageProfile) -> SiteInfo {
+        return SiteInfo {
+            namespaces: language.namespaces.clone()
+        };
+    }
+
+    /// Finds the namespace of a page from the prefix of its title.
+    /// Only needed for old dumps where pages do not have a `<ns>` element.
+    ///
+    /// # Returns
+    /// The namespace key, 0 (the main namespace) if the title has no namespace prefix.
+    fn namespace_of(&self, title: &str) -> i32 {
+        if let Some(position) = title.find(':') {
+            if let Some(key) = self.namespaces.get(title[..position].trim()) {
+                return *key;
+            }
+        }
+        return 0;
+    }
+}
+
+/// The type of a valid page found during the first pass over the dump
+enum PageKind {
+    /// Article with its raw link targets (only collected for single pass parsing)
+    Article(Vec<String>),
+    /// Redirect to the contained article name
+    Redirect(String)
+}
+
+/// Direction of the links of each article in a parsed dataset
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ParserMode {
+    /// Links to each article from other articles
     IncomingLinks,
+    /// Links from each article to other articles
     OutgoingLinks
 }
 
+impl ParserMode {
+    /// Gets the direction of the graph the links of this mode are in
+    pub fn direction(&self) -> Direction {
+        return match self {
+            ParserMode::IncomingLinks => Direction::Incoming,
+            ParserMode::OutgoingLinks => Direction::Outgoing
+        };
+    }
+}
+
+/// How many times the dump is read when parsing, see `parse_xml_dump()`
+pub enum PassMode {
+    /// Read the dump twice, once to find all pages and once to find links
+    TwoPass,
+    /// Read the dump once, holding raw link targets in memory until all pages are known
+    SinglePass,
+    /// Read the dump once, spilling raw link targets to the given temporary file
+    SinglePassSpill(PathBuf)
+}
+
+/// Options controlling how a dump is parsed, see `parse_xml_dump()`
+pub struct ParseOptions {
+    /// Path to the index of a multistream dump, if given the dump is parsed in parallel
+    pub index_path: Option<String>,
+    /// Rules for the article names to ignore when constructing the graph
+    pub articles_to_ignore: Option<IgnoreRules>,
+    /// What the output representation should be, a list of incoming links or outgoing links
+    pub mode: ParserMode,
+    /// Whether the dump is read once or twice
+    pub pass_mode: PassMode,
+    /// Keys of the namespaces to accept pages from, by default only articles (namespace 0)
+    pub namespaces: HashSet<i32>,
+    /// Localized magic words and template names of the wikipedia the dump is from
+    pub language: LanguageProfile,
+    /// Path to write a report of broken and cyclic redirects to, see `write_redirect_report()`
+    pub redirect_report_path: Option<String>,
+    /// Path to write a report of the pages each ignore rule matched to, see `IgnoreRules::write_report()`
+    pub ignore_report_path: Option<String>,
+    /// Skip malformed pages instead of stopping with an error
+    pub skip_bad_pages: bool,
+    /// Report progress and a summary of the parse on stderr, see `Progress`
+    pub report_progress: bool,
+    /// Path to write the summary of the parse to as JSON
+    pub summary_json_path: Option<String>
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        return ParseOptions {
+            index_path: None,
+            articles_to_ignore: None,
+            mode: ParserMode::IncomingLinks,
+            pass_mode: PassMode::TwoPass,
+            namespaces: [0].iter().copied().collect(),
+            language: LanguageProfile::built_in("en").unwrap(),
+            redirect_report_path: None,
+            ignore_report_path: None,
+            skip_bad_pages: false,
+            report_progress: false,
+            summary_json_path: None
+        };
+    }
+}
+
+/// Redirects that could not be resolved to an article, see `resolve_redirects()`.
+///
+/// Each entry is (redirect name, chain) where the chain is the names the redirect passes
+/// through, starting with the page it redirects to.
+#[derive(Debug, Default)]
+pub struct RedirectReport {
+    /// Redirects whose chain ends at a page that is neither an article nor a redirect
+    pub broken: Vec<(String, Vec<String>)>,
+    /// Redirects whose chain loops back on itself. The last name of the chain is the
+    /// name (either the redirect or a name earlier in the chain) it loops back to.
+    pub cyclic: Vec<(String, Vec<String>)>
+}
+
+/// Finds links and special templates in the body of a page.
+/// Holds the compiled regexes so they are only compiled once per parse.
+struct LinkExtractor {
+    link_regex: Regex,
+    redirect_regex: Regex,
+    infobox_regex: Option<Regex>,
+    main_article_regex: Option<Regex>,
+    see_also_regex: Option<Regex>,
+    disambiguation_regex: Option<Regex>
+}
+
+impl LinkExtractor {
+
+    /// Compiles the regexes for the templates of the given language.
+    /// Templates with no names in the language profile are never matched.
+    fn new(language: &LanguageProfile) -> LinkExtractor {
+        let template_regex = | names: &[String], pattern: &str | -> Option<Regex> {
+            if names.is_empty() {
+                return None;
+            }
+            let names: Vec<String> = names.iter().map(|x| template_name_pattern(x)).collect();
+            return Some(Regex::new(&pattern.replace("NAMES", &names.join("|"))).unwrap());
+        };
+
+        // Disambiguation template names ending in * match any template starting with the name,
+        // otherwise the name must be followed by the end of the template or its first parameter
+        let disambiguation_names: Vec<String> = language.disambiguation_templates
+            .iter()
+            .map(|x| match x.strip_suffix('*') {
+                Some(prefix) => template_name_pattern(prefix),
+                None => format!(r"{}\s*(?:\}}\}}|\|)", template_name_pattern(x))
+            })
+            .collect();
+
+        return LinkExtractor {
+            link_regex: Regex::new(r"[^=]\[\[([^\[\]]+)\]\]").unwrap(),
+            redirect_regex: Regex::new(r"\[\[([^\[\]]+)\]\]").unwrap(),
+            infobox_regex: template_regex(&language.infobox_templates, r"(?ms)\{\{(?:NAMES).*?^\}\}"),
+            main_article_regex: template_regex(&language.main_article_templates, r"\{\{(?:NAMES)\|([^{}\|]+?)\}\}"),
+            see_also_regex: template_regex(&language.see_also_templates, r"\{\{(?:NAMES)\|([^\{\}]+?)\}\}"),
+            disambiguation_regex: match disambiguation_names.is_empty() {
+                true => None,
+                false => Some(Regex::new(&format!(r"\{{\{{(?:{})", disambiguation_names.join("|"))).unwrap())
+            }
+        };
+    }
+
+    /// Checks if a page body contains a disambiguation template.
+    fn is_disambiguation(&self, body: &str) -> bool {
+        return match &self.disambiguation_regex {
+            Some(disambiguation_regex) => disambiguation_regex.is_match(body),
+            None => false
+        };
+    }
+
+    /// Gets the article name a redirect page body redirects to, this is the first link in the body.
+    ///
+    /// # Returns
+    /// The redirect target or None if the body has no link.
+    fn redirect_target(&self, body: &str) -> Option<String> {
+        let target = self.redirect_regex
+            .captures(body)?
+            .get(1)
+            .unwrap()
+            .as_str()
+            .split("|").next().unwrap()  // Select article name
+            .split("#").next().unwrap()       // Strip in page anchor
+            .trim()
+            .to_string()
+            .capitalize_first_letter();
+        return Some(target);
+    }
+
+    /// Gets the raw link targets in a page body, these may not be valid article names.
+    /// Links in the infobox are skipped.
+    fn links(&self, body: &str) -> Vec<String> {
+        // Skip infobox if present
+        let infobox = self.infobox_regex.as_ref().and_then(|x| x.shortest_match(body));
+        let body = match infobox {
+            Some(end_position) => &body[end_position..],
+            None => body
+        };
+
+        // Article links are of the form:
+        // [[article name#optional_anchor|display name]]
+        let mut links: Vec<String> = self.link_regex
+            .captures_iter(body)
+            .map(|x| x
+                .get(1)
+                .unwrap()
+                .as_str()
+                .split("|").next().unwrap()  // Select article name
+                .split("#").next().unwrap()       // Strip in page anchor
+                .trim()
+                .to_string()
+                .capitalize_first_letter())
+            .collect();
+
+        for capture in self.main_article_regex.iter().flat_map(|x| x.captures_iter(body)) {
+            let link = capture
+                .get(1)
+                .unwrap()
+                .as_str()
+                .split("#").next().unwrap()       // Strip in page anchor
+                .trim()
+                .to_string();
+            links.push(link.to_string());
+        }
+
+        for capture in self.see_also_regex.iter().flat_map(|x| x.captures_iter(body)) {
+            for link in capture.get(1).unwrap().as_str().split("|") {
+                links.push(link.split("#").next().unwrap().trim().to_string());
+            }
+        }
+        return links;
+    }
+}
+
+/// A parsed dump: (article name -> article index, adjacency list of links, redirect name -> article index),
+/// see `parse_xml_dump()` and `load_from_tsv()`
+pub type Dataset = (HashMap<String, u32>, Vec<Article>, HashMap<String, u32>);
+
 pub struct Article {
     /// This is part of an adjacency list representation of the link graph
     /// Links are identified by their index in this vector
@@ -31,55 +309,50 @@ pub struct Article {
 /// Approximate number of articles in the 2017_11_03 wikipedia XML dump
 const NUM_ARTICLES: u32 = 6_000_000;
 
+/// Number of multistream dump streams (of up to 100 pages each) to queue per worker thread
+const STREAMS_PER_THREAD: usize = 16;
+
+/// Creates a regex pattern matching a template name.
+/// Like wikipedia titles, the first letter of a template name may be either case.
+fn template_name_pattern(name: &str) -> String {
+    let mut char_iter = name.chars();
+    return match char_iter.next() {
+        None => String::new(),
+        Some(chr) => {
+            let first_letter: String = chr.to_uppercase().chain(chr.to_lowercase()).collect();
+            format!("[{}]{}", regex::escape(&first_letter), regex::escape(char_iter.as_str()))
+        }
+    };
+}
+
 /// Checks if a given title is 'valid' for my definition of valid in relation to this project.
 ///
 /// Returns `true` if the title is valid, `false` otherwise.
 ///
 /// # Arguments
 /// * `title` - The page title with first character capitalized
+/// * `language` - Language profile with the localized disambiguation and list title markers
 ///
 /// # Remarks
-/// In general a 'valid' page is an encyclopedia article, I try to avoid any meta pages relating
-/// to wikipedia itself. I also try to avoid picture links and disambiguation pages.
+/// In general a 'valid' page is an encyclopedia article, I try to avoid lists and disambiguation pages.
+/// Meta pages relating to wikipedia itself (files, categories, templates, etc) are excluded by
+/// namespace instead, see `scan_pages()`.
 ///
 /// Note that wikipedia links are case sensitive except for the first letter. It is preferred
 /// that articles have the first letter capitalized to match the wikipedia style guide.
 ///
-fn is_valid_title(title: &str) -> bool {
+fn is_valid_title(title: &str, language: &LanguageProfile) -> bool {
     if title.len() == 0 {
         return false;
     }
-    if let Some(_) = title.find(":") {
-        // Not the most efficient but doesn't take unreasonably
-        // long for the moment as the parsing XML step should only be run once
-        if title.starts_with("File") ||
-            title.starts_with("Discussion") ||
-            title.starts_with("Image") ||
-            title.starts_with("Category") ||
-            title.starts_with("Wikipedia") ||
-            title.starts_with("Portal") ||
-            title.starts_with("Template") ||
-            title.starts_with("Draft") ||
-            title.starts_with("Module") ||
-            title.starts_with("User") ||
-            title.starts_with("Commons") ||
-            title.starts_with("Wikt") ||
-            title.starts_with("Book") ||
-            title.starts_with("Mediawiki") ||
-            title.starts_with("User talk"){
-            return false;
-        }
-    }
     if let Some(_) = title.find("\n") {
         return false
     }
     if let Some(_) = title.find("\t") {
         return false
     }
-    if title.contains("(disambiguation)") ||
-       title.starts_with("List of") ||
-       title.starts_with("Index of") ||
-       title.starts_with("Table of") {
+    if language.disambiguation_titles.iter().any(|x| title.contains(x.as_str())) ||
+       language.list_prefixes.iter().any(|x| title.starts_with(x.as_str())) {
         return false;
     }
     return true;
@@ -103,57 +376,180 @@ impl StringExt for String {
 
 /// Scans through pages in a given wikipedia XML dump and calls
 /// the given callback for each valid page. A valid page is one
-/// that passes the `is_valid_title()` check.
+/// that passes the `is_valid_title()` check and is in one of the accepted namespaces.
+///
+/// The dump may be plain XML or compressed with bzip2/gzip, see `dump::open_dump()`.
 ///
 /// # Arguments
 /// * `xml_path` - Path to the unprocessed XML database dump
+/// * `filter` - Decides which pages are valid
+/// * `valid_page_callback` - A callback that is executed for every valid page
+///
+/// # Returns
+/// The errors of the malformed pages that were skipped, always empty unless `filter.skip_bad_pages` is set.
+///
+fn scan_pages<F>(
+    xml_path: &String,
+    filter: &PageFilter,
+    valid_page_callback: F) -> ParseResult<Vec<ParseError>>
+    where F: FnMut(Page){
+    let buf_reader = dump::open_dump_counted(Path::new(xml_path), filter.progress.bytes_read_counter())
+        .map_err(|e| ParseError::io(xml_path, e))?;
+    let mut siteinfo = SiteInfo::from_language(filter.language);
+    return scan_reader(Reader::from_reader(buf_reader), xml_path, None, &mut siteinfo, filter, valid_page_callback);
+}
+
+/// Scans through pages from an XML reader, see `scan_pages()`.
+///
+/// A malformed page returns an error, or if `filter.skip_bad_pages` is set the rest of the page is
+/// skipped and scanning continues. Errors that do not belong to a single page (eg failing to read
+/// the underlying file) are always returned.
+///
+/// # Arguments
+/// * `reader` - XML reader positioned at the start of the dump (or a stream of a multistream dump)
+/// * `xml_path` - Path to the dump being read, used in errors
+/// * `stream` - Byte offset of the stream being read for multistream dumps, used in errors
+/// * `siteinfo` - Site information used to find the namespace of pages without a `<ns>` element,
+///   updated if a `<siteinfo>` block is read.
+/// * `filter` - Decides which pages are valid
 /// * `valid_page_callback` - A callback that is executed for every valid page
 ///
-fn scan_pages<F>(xml_path: &String, mut valid_page_callback: F) -> ()
-    where F: FnMut(String, String){
-    let file = File::open(xml_path).unwrap();
-    let buf_reader = BufReader::new(file);
-    let mut reader =  Reader::from_reader(buf_reader);
+/// # Returns
+/// The errors of the malformed pages that were skipped
+///
+fn scan_reader<R, F>(
+    mut reader: Reader<R>,
+    xml_path: &str,
+    stream: Option<u64>,
+    siteinfo: &mut SiteInfo,
+    filter: &PageFilter,
+    mut valid_page_callback: F) -> ParseResult<Vec<ParseError>>
+    where R: BufRead, F: FnMut(Page){
 
     let mut source_article_name: Option<String> = None;
+    let mut source_namespace: Option<i32> = None;
+    let mut source_redirect: Option<String> = None;
     let mut parser_state = ParserState::Idle;
 
+    // Title of the current page as written in the dump (even if invalid), for errors
+    let mut current_title: Option<String> = None;
+    let mut skipped_pages: Vec<ParseError> = Vec::new();
+    let mut skipping_page = false;
+    let mut last_error_position: Option<usize> = None;
+
     loop {
         let mut buf = Vec::new();
-        match reader.read_event(&mut buf) {
+        let page_error = | message: String, title: &Option<String>, reader: &Reader<R> | ParseError::Page {
+            title: title.clone(),
+            stream,
+            offset: reader.buffer_position(),
+            message
+        };
+
+        let result: ParseResult<()> = match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => {
                 match e.name() {
-                    b"title" => parser_state = ParserState::ReadingTitle,
+                    b"page" => {
+                        filter.progress.page_read();
+                        source_article_name = None;
+                        source_namespace = None;
+                        source_redirect = None;
+                        current_title = None;
+                        skipping_page = false;
+                        Ok(())
+                    },
+                    b"redirect" => redirect_title(e, &reader)
+                        .map(|redirect| source_redirect = redirect)
+                        .map_err(|message| page_error(message, &current_title, &reader)),
+                    b"title" => {
+                        parser_state = ParserState::ReadingTitle;
+                        Ok(())
+                    },
+                    b"ns" => {
+                        parser_state = ParserState::ReadingNamespace;
+                        Ok(())
+                    },
+                    b"namespace" => namespace_key(e, &reader)
+                        .map(|key| parser_state = ParserState::ReadingNamespaceName(key))
+                        .map_err(|message| page_error(message, &current_title, &reader)),
                     b"text" => {
                         match source_article_name {
                             Some(_) => parser_state = ParserState::ReadingBody,
                             None => ()
                         }
+                        Ok(())
                     },
-                    _ => (),
+                    _ => Ok(()),
+                }
+            }
+
+            Ok(Event::Empty(ref e)) => {
+                match e.name() {
+                    // Redirect pages are marked with <redirect title="..." />
+                    b"redirect" => redirect_title(e, &reader)
+                        .map(|redirect| source_redirect = redirect)
+                        .map_err(|message| page_error(message, &current_title, &reader)),
+                    // The main namespace has no name so is written as <namespace key="0" ... />
+                    b"namespace" => namespace_key(e, &reader)
+                        .map(|key| {
+                            siteinfo.namespaces.insert(String::new(), key);
+                        })
+                        .map_err(|message| page_error(message, &current_title, &reader)),
+                    _ => Ok(())
                 }
             }
 
             Ok(Event::Text(e)) => {
-                match parser_state {
+                let state = mem::replace(&mut parser_state, ParserState::Idle);
+                match state {
                     ParserState::ReadingTitle => {
-                        // Wikipedia does not care about the case of
-                        // the first letter in the title. Generally
-                        // sentence case is preferred for article titles,
-                        // so capitalize first letter if it is not already.
-                        // We must do this because page links can appear
-                        // as upper case or lower case.
-                        let article_name = e.unescape_and_decode(&reader)
-                            .unwrap()
-                            .trim()
-                            .to_string()
-                            .capitalize_first_letter();
-
-                        if is_valid_title(&article_name) {
-                            source_article_name = Some(article_name);
+                        match e.unescape_and_decode(&reader) {
+                            Ok(title) => {
+                                // Wikipedia does not care about the case of
+                                // the first letter in the title. Generally
+                                // sentence case is preferred for article titles,
+                                // so capitalize first letter if it is not already.
+                                // We must do this because page links can appear
+                                // as upper case or lower case.
+                                let article_name = title
+                                    .trim()
+                                    .to_string()
+                                    .capitalize_first_letter();
+                                current_title = Some(title);
+
+                                if is_valid_title(&article_name, filter.language) {
+                                    source_article_name = Some(article_name);
+                                }
+                                else {
+                                    source_article_name = None;
+                                }
+                                Ok(())
+                            },
+                            Err(e) => Err(page_error(format!("Invalid title: {}", e), &current_title, &reader))
                         }
-                        else {
-                            source_article_name = None;
+                    }
+
+                    ParserState::ReadingNamespace => {
+                        match e.unescape_and_decode(&reader) {
+                            Ok(namespace) => match namespace.trim().parse::<i32>() {
+                                Ok(namespace) => {
+                                    source_namespace = Some(namespace);
+                                    Ok(())
+                                },
+                                Err(_) => Err(page_error(
+                                    format!("Invalid namespace '{}'", namespace.trim()), &current_title, &reader))
+                            },
+                            Err(e) => Err(page_error(format!("Invalid namespace: {}", e), &current_title, &reader))
+                        }
+                    }
+
+                    ParserState::ReadingNamespaceName(key) => {
+                        match e.unescape_and_decode(&reader) {
+                            Ok(name) => {
+                                siteinfo.namespaces.insert(name.trim().to_string(), key);
+                                Ok(())
+                            },
+                            Err(e) => Err(page_error(format!("Invalid namespace name: {}", e), &current_title, &reader))
                         }
                     }
 
@@ -162,40 +558,234 @@ fn scan_pages<F>(xml_path: &String, mut valid_page_callback: F) -> ()
                             .take()
                             .expect("Article must be defined");
 
-                        let body = e.unescape_and_decode(&reader).unwrap();
+                        // Old dumps do not have <ns> elements so fall back on the title prefix
+                        let namespace = match source_namespace {
+                            Some(namespace) => namespace,
+                            None => siteinfo.namespace_of(&source_article_name)
+                        };
 
-                        valid_page_callback(source_article_name, body);
+                        if filter.namespaces.contains(&namespace) {
+                            match e.unescape_and_decode(&reader) {
+                                Ok(body) => {
+                                    valid_page_callback(Page {
+                                        title: source_article_name,
+                                        redirect: source_redirect.take(),
+                                        body
+                                    });
+                                    Ok(())
+                                },
+                                Err(e) => Err(page_error(format!("Invalid text: {}", e), &current_title, &reader))
+                            }
+                        }
+                        else {
+                            Ok(())
+                        }
                     },
-                    _ => ()
+                    _ => Ok(())
                 }
-                parser_state = ParserState::Idle;
             },
             Ok(Event::End(_)) => {
-                parser_state = ParserState::Idle
+                parser_state = ParserState::Idle;
+                Ok(())
             },
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            _ => (), // There are several other `Event`s we do not consider here
+            Err(quick_xml::Error::Io(e)) => Err(ParseError::io(xml_path, e)),
+            Err(e) => Err(page_error(e.to_string(), &current_title, &reader)),
+            _ => Ok(()), // There are several other `Event`s we do not consider here
+        };
+
+        if let Err(error) = result {
+            // The reader must move forward after an error or it would report the same error forever
+            let position = reader.buffer_position();
+            if !filter.skip_bad_pages || !error.is_page_error() || last_error_position == Some(position) {
+                return Err(error);
+            }
+            last_error_position = Some(position);
+
+            // Ignore the rest of the page, a page may cause several errors but is only skipped once
+            source_article_name = None;
+            source_redirect = None;
+            parser_state = ParserState::Idle;
+            if !skipping_page {
+                skipping_page = true;
+                skipped_pages.push(error);
+            }
         }
         buf.clear();
     }
+    return Ok(skipped_pages);
 }
 
-/// Parses a wikipedia XML database dump into an adjacency list of links.
+/// Reads the `title` attribute of a page's `<redirect>` element
+fn redirect_title<R: BufRead>(
+    element: &BytesStart,
+    reader: &Reader<R>) -> std::result::Result<Option<String>, String> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid redirect attribute: {}", e))?;
+        if attribute.key == b"title" {
+            return attribute
+                .unescape_and_decode_value(reader)
+                .map(Some)
+                .map_err(|e| format!("Invalid redirect title: {}", e));
+        }
+    }
+    return Ok(None);
+}
+
+/// Reads the `key` attribute of a `<namespace>` element in the `<siteinfo>` block
+fn namespace_key<R: BufRead>(element: &BytesStart, reader: &Reader<R>) -> std::result::Result<i32, String> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid namespace attribute: {}", e))?;
+        if attribute.key == b"key" {
+            let key = attribute
+                .unescape_and_decode_value(reader)
+                .map_err(|e| format!("Invalid namespace key: {}", e))?;
+            return key
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid namespace key '{}'", key));
+        }
+    }
+    return Err(String::from("Namespace without a key"));
+}
+
+/// Reads the database name of the wiki (eg enwiki) from the `<siteinfo>` block of a dump.
+/// Only the start of the dump is read.
+///
+/// # Arguments
+/// * `xml_path` - Path to the unprocessed XML database dump
+///
+/// # Returns
+/// The database name, or None if the `<siteinfo>` block does not have a `<dbname>`
+///
+pub fn read_site_name(xml_path: &str) -> ParseResult<Option<String>> {
+    let buf_reader = dump::open_dump(Path::new(xml_path)).map_err(|e| ParseError::io(xml_path, e))?;
+    let mut reader = Reader::from_reader(buf_reader);
+    let mut reading_name = false;
+
+    loop {
+        let mut buf = Vec::new();
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"dbname" => reading_name = true,
+                    b"page" => return Ok(None),
+                    _ => ()
+                }
+            },
+            Ok(Event::Text(e)) if reading_name => {
+                return e.unescape_and_decode(&reader)
+                    .map(|name| Some(name.trim().to_string()))
+                    .map_err(|e| ParseError::format(xml_path, format!("Invalid dbname: {}", e)));
+            },
+            Ok(Event::End(ref e)) if e.name() == b"siteinfo" => return Ok(None),
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => return Err(ParseError::format(xml_path, format!("Invalid XML: {}", e))),
+            _ => ()
+        }
+    }
+}
+
+/// Runs a function over every valid page in a dump then passes the results to `apply`.
+///
+/// If the stream ranges of a multistream dump are given the streams are scanned in parallel,
+/// otherwise the dump is scanned serially using `scan_pages()`. In both cases `apply` is called
+/// serially with results in the same order as the pages appear in the dump, so the output
+/// does not depend on whether the dump was scanned in parallel.
 ///
 /// # Arguments
 /// * `xml_path` - Path to the unprocessed XML database dump
-/// * `articles_to_ignore` - A hashset of article names to ignore when constructing the graph.
-/// * `mode` - What the output representation should be, a list of incoming links or outgoing links
+/// * `streams` - Byte ranges of the bzip2 streams of a multistream dump, see `dump::multistream_ranges()`
+/// * `filter` - Decides which pages are valid
+/// * `map_page` - Function executed (possibly in parallel) for every valid page
+/// * `apply` - Callback executed in dump order for every result returned by `map_page`
+///
+/// # Returns
+/// The errors of the malformed pages that were skipped, in dump order
+///
+fn for_each_page<T, M, A>(
+    xml_path: &String,
+    streams: Option<&[(u64, u64)]>,
+    filter: &PageFilter,
+    map_page: M,
+    mut apply: A) -> ParseResult<Vec<ParseError>>
+    where T: Send, M: Fn(Page) -> Option<T> + Sync, A: FnMut(T) {
+
+    let streams = match streams {
+        Some(streams) => streams,
+        None => {
+            return scan_pages(xml_path, filter, | page | {
+                if let Some(result) = map_page(page) {
+                    apply(result);
+                }
+                filter.progress.maybe_report();
+            });
+        }
+    };
+
+    let scan_stream = | range: (u64, u64), siteinfo: &mut SiteInfo |
+        -> ParseResult<(Vec<T>, Vec<ParseError>)> {
+        let stream = dump::open_stream(Path::new(xml_path), range, filter.progress.bytes_read_counter())
+            .map_err(|e| ParseError::io(xml_path, e))?;
+        let mut reader = Reader::from_reader(stream);
+        // Each stream is only a fragment of the document so the
+        // start and end tags of the root element are not matched
+        reader.check_end_names(false);
+
+        let mut stream_results = Vec::new();
+        let skipped_pages = scan_reader(reader, xml_path, Some(range.0), siteinfo, filter, | page | {
+            if let Some(result) = map_page(page) {
+                stream_results.push(result);
+            }
+        })?;
+        return Ok((stream_results, skipped_pages));
+    };
+
+    // The first stream holds the <siteinfo> header, which is needed by every other stream
+    let mut siteinfo = SiteInfo::from_language(filter.language);
+    let (header_stream, streams) = match streams.split_first() {
+        Some(split) => split,
+        None => return Ok(Vec::new())
+    };
+    let (header_results, mut skipped_pages) = scan_stream(*header_stream, &mut siteinfo)?;
+    for result in header_results {
+        apply(result);
+    }
+
+    // Streams are processed in batches so the results held in memory at any time are bounded
+    let batch_size = rayon::current_num_threads() * STREAMS_PER_THREAD;
+    for batch in streams.chunks(batch_size) {
+        let results: Vec<(Vec<T>, Vec<ParseError>)> = batch
+            .par_iter()
+            .map(|range| scan_stream(*range, &mut siteinfo.clone()))
+            .collect::<ParseResult<_>>()?;
+
+        for (stream_results, stream_skipped_pages) in results {
+            for result in stream_results {
+                apply(result);
+            }
+            skipped_pages.extend(stream_skipped_pages);
+        }
+        filter.progress.maybe_report();
+    }
+    return Ok(skipped_pages);
+}
+
+/// Parses a wikipedia XML database dump into an adjacency list of links.
+///
+/// # Arguments
+/// * `xml_path` - Path to the unprocessed XML database dump, may be compressed (`.bz2` or `.gz`)
+/// * `options` - Options controlling how the dump is parsed, see `ParseOptions`
 ///
 /// # Returns
 ///  * A HashMap of article name -> article index
 ///  * An adjacency list representation of the links to/from each article.
+///  * A HashMap of redirect name -> article index (of the article the redirect resolves to)
 ///
-/// # Panics
-/// There are several potential panics from regexes relating to the format of text within the XML document.
-/// This function has only been tested with the 2017-11-03 pages-articles-multistream XML dump and
-/// does not panic with this dataset, but may with others if they do not follow the same format.
+/// # Errors
+/// Returns an error if the dump (or multistream index) cannot be read or a page is malformed.
+/// Malformed pages are skipped instead if `skip_bad_pages` is set in the options, the errors are
+/// printed and the number of skipped pages is reported at the end.
+/// This function has only been tested with the 2017-11-03 pages-articles-multistream XML dump.
 ///
 /// # Remarks
 ///
@@ -209,11 +799,19 @@ fn scan_pages<F>(xml_path: &String, mut valid_page_callback: F) -> ()
 /// * Links inside infoboxes (the box on the right of a page, usually with information about places of interest)
 /// * Links from disambiguation pages
 ///
-/// This function performs two passes over the database dump. The first pass finds all valid pages
+/// By default this function performs two passes over the database dump. The first pass finds all valid pages
 /// (including redirects). Before the second pass the redirects are 'forwarded' through the graph
 /// until they point to a real page. For all links, if no real page is found to match then the link
 /// is not added. In practise there are many more empty links than real page links.
-/// 
+///
+/// With `PassMode::SinglePass` the raw link targets of each article are collected during the first
+/// pass instead (see `LinkStore`) and resolved once all pages and redirects are known, so the dump
+/// is only read once. The output is identical to a two pass parse.
+///
+/// If the index of a `pages-articles-multistream` dump is given, each pass scans the independent
+/// bzip2 streams of the dump in parallel. Results are merged in dump order so the output is
+/// identical to a serial parse.
+///
 /// Most functions in `WikipediaAnalysis` were designed for the incoming link adjacency list
 /// representation was as it is easier to process (for my intended use cases).
 /// With this representation parsing is harder as state must be maintained
@@ -222,14 +820,59 @@ fn scan_pages<F>(xml_path: &String, mut valid_page_callback: F) -> ()
 ///
 pub fn parse_xml_dump(
     xml_path: &String,
-    articles_to_ignore: Option<HashSet<String>>,
-    mode: ParserMode) -> (HashMap<String, u32>, Vec<Article>) {
+    options: ParseOptions) -> ParseResult<Dataset> {
+
+    let ParseOptions {
+        index_path,
+        articles_to_ignore,
+        mode,
+        pass_mode,
+        namespaces,
+        language,
+        redirect_report_path,
+        ignore_report_path,
+        skip_bad_pages,
+        report_progress,
+        summary_json_path
+    } = options;
+
+    let dump_size = std::fs::metadata(xml_path).map_err(|e| ParseError::io(xml_path, e))?.len();
+    let progress = Progress::new(dump_size, report_progress);
+    let stats = &progress.stats;
+    let filter = PageFilter {
+        namespaces: &namespaces,
+        language: &language,
+        skip_bad_pages,
+        progress: &progress
+    };
 
     // Compile regexes once for efficiency
-    let link_regex = Regex::new(r"[^=]\[\[([^\[\]]+)\]\]").unwrap();
-    let infobox_regex = Regex::new(r"(?ms)\{\{Infobox.*?^\}\}").unwrap();
-    let main_article_regex = Regex::new(r"\{\{main article\|([^{}\|]+?)\}\}").unwrap();
-    let see_also_regex = Regex::new(r"\{\{see also\|([^\{\}]+?)\}\}").unwrap();
+    let extractor = LinkExtractor::new(&language);
+
+    // Split a multistream dump into its streams once, both passes use the same streams
+    let streams = match index_path {
+        Some(index_path) => {
+            let offsets = dump::read_multistream_index(Path::new(&index_path))
+                .map_err(|e| ParseError::io(&index_path, e))?;
+            Some(dump::multistream_ranges(Path::new(xml_path), &offsets)
+                .map_err(|e| ParseError::io(xml_path, e))?)
+        },
+        None => None
+    };
+
+    let spill_path = match &pass_mode {
+        PassMode::SinglePassSpill(spill_path) => spill_path.to_string_lossy().to_string(),
+        _ => String::new()
+    };
+    let mut link_store = match &pass_mode {
+        PassMode::TwoPass => None,
+        PassMode::SinglePass => Some(LinkStore::in_memory()),
+        PassMode::SinglePassSpill(spill_path) => Some(LinkStore::spill_to(spill_path.clone())
+            .map_err(|e| ParseError::io(&spill_path.to_string_lossy(), e))?)
+    };
+    // First error writing to the link store, the pass continues but the error is returned at the end
+    let mut link_store_error: Option<Error> = None;
+    let collect_links = link_store.is_some();
 
     // Maps name of article => index of Article struct in articles
     let mut article_map: HashMap<String, u32> = HashMap::with_capacity(NUM_ARTICLES as usize);
@@ -237,53 +880,66 @@ pub fn parse_xml_dump(
     let mut redirect_to: HashMap<String, String> = HashMap::with_capacity(NUM_ARTICLES as usize);
     let mut articles: Vec<Article> = Vec::with_capacity(NUM_ARTICLES as usize);
 
-    let get_valid_pages = | article_name: String, body: String | -> () {
+    let get_valid_pages = | page: Page | -> Option<(String, PageKind)> {
+        let Page { title: article_name, redirect, body } = page;
 
         // First check if this is an article to be ignored
         if let Some(to_ignore) = &articles_to_ignore {
-            if to_ignore.contains(&article_name) {
-                return;
+            if to_ignore.matches(&article_name) {
+                ParseStats::add(&stats.ignored, 1);
+                return None;
             }
         }
 
-        // Redirect pages must start with #redirect followed by
-        // the page they are redirecting to. No other text is allowed.
-        // Case of redirect doesnt matter but im assuming no one will
-        // do anything silly like rEdIrEcT
-        let is_redirect =
-            body.starts_with("#redirect") ||
-            body.starts_with("#REDIRECT");
+        // Modern dumps mark redirects with a <redirect title="..."> element which is
+        // authoritative. Old dumps without it need the body to be checked instead,
+        // redirect pages must start with #redirect (or the localized magic word)
+        // followed by the page they are redirecting to. No other text is allowed.
+        let redirected_to_article_name = match redirect {
+            Some(redirect) => Some(redirect.trim().to_string().capitalize_first_letter()),
+            None => match language.is_redirect(body.trim_start()) {
+                true => extractor.redirect_target(&body),
+                false => None
+            }
+        };
 
         // https://simple.wikipedia.org/wiki/MediaWiki:Disambiguationspage
-        // This should cover most uses
-        let is_disambiguation =
-            body.contains("{{disamb") ||
-            body.contains("{{Disamb") ||
-            body.contains("{{dab}}");
+        // The templates in the language profile should cover most uses
+        let is_disambiguation = extractor.is_disambiguation(&body);
 
-        if is_redirect && link_regex.is_match(&body){
+        if let Some(redirected_to_article_name) = redirected_to_article_name {
             // If the page is a redirect then there is one outgoing link
             // to the page any incoming links should be redirected to
-            let redirected_to_article_name: String = link_regex
-                .captures(&body)
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str()
-                .split("|").next().unwrap()  // Select article name
-                .split("#").next().unwrap()       // Strip in page anchor
-                .trim()
-                .to_string()
-                .capitalize_first_letter();
+            if is_valid_title(&redirected_to_article_name, &language) {
+                ParseStats::add(&stats.redirects, 1);
+                return Some((article_name, PageKind::Redirect(redirected_to_article_name)));
+            }
+            return None;
+        }
+
+        // Normal article page
+        else if !is_disambiguation {
+            let links = match collect_links {
+                true => extractor.links(&body),
+                false => Vec::new()
+            };
+            ParseStats::add(&stats.articles, 1);
+            return Some((article_name, PageKind::Article(links)));
+        }
+        ParseStats::add(&stats.disambiguations, 1);
+        return None;
+    };
 
-            if is_valid_title(&redirected_to_article_name) {
+    let add_valid_page = | (article_name, kind): (String, PageKind) | -> () {
+        match kind {
+            PageKind::Redirect(redirected_to_article_name) => {
                 let insert_result = redirect_to.insert(
                     article_name.clone(),
                     redirected_to_article_name.clone()
                 );
                 match insert_result {
                     Some(old) => {
-                        println!(
+                        eprintln!(
                             "Multiple page redirects, should not happen: {}: {}, {}",
                             article_name,
                             old,
@@ -292,27 +948,53 @@ pub fn parse_xml_dump(
                     },
                     None => ()
                 }
-            }
-        }
-
-        // Normal article page
-        else if !is_disambiguation {
-            match article_map.get(&article_name) {
-                Some(_) => println!("Multiple page insertions, should not happen: {}", article_name),
-                None => {
-                    article_map.insert(
-                        article_name,
-                        article_map.len().try_into().unwrap()
-                    );
-                    articles.push(Article {
-                        links: Vec::new()
-                    });
+            },
+            PageKind::Article(links) => {
+                let article_index = match article_map.get(&article_name) {
+                    Some(article_index) => {
+                        eprintln!("Multiple page insertions, should not happen: {}", article_name);
+                        *article_index
+                    },
+                    None => {
+                        let article_index: u32 = article_map.len().try_into().unwrap();
+                        article_map.insert(article_name, article_index);
+                        articles.push(Article {
+                            links: Vec::new()
+                        });
+                        article_index
+                    }
+                };
+                if let Some(link_store) = &mut link_store {
+                    if let Err(e) = link_store.push(article_index, &links) {
+                        link_store_error.get_or_insert(e);
+                    }
                 }
             }
         }
     };
 
-    scan_pages(xml_path, get_valid_pages);
+    let pass_count = match collect_links {
+        true => 1,
+        false => 2
+    };
+    progress.start_pass(&format!("pass 1/{}", pass_count));
+    let skipped_pages = for_each_page(xml_path, streams.as_deref(), &filter, get_valid_pages, add_valid_page)?;
+    progress.finish_pass();
+    ParseStats::add(&stats.skipped, skipped_pages.len() as u64);
+    if let Some(e) = link_store_error {
+        return Err(ParseError::io(&spill_path, e));
+    }
+    for skipped_page in skipped_pages.iter() {
+        eprintln!("Skipped {}", skipped_page);
+    }
+    if let Some(to_ignore) = &articles_to_ignore {
+        if report_progress {
+            to_ignore.print_summary();
+        }
+        if let Some(ignore_report_path) = ignore_report_path {
+            to_ignore.write_report(&ignore_report_path)?;
+        }
+    }
 
     // Finally parse articles again for their links
     // Place each outgoing link as an incoming link in the graph with
@@ -321,125 +1003,132 @@ pub fn parse_xml_dump(
     // then source and destination article are swapped
     // Any links to redirects are redirected towards the real article after
     // following the redirects
-    let redirects_map = resolve_redirects(&article_map, &mut redirect_to);
-
-    let add_links = | article_name: String, body: String | -> () {
-
-        let source_article_index = match article_map.get(&article_name) {
-            Some(source_article_index) => source_article_index,
-            None => return
-        };
-
-        // Skip infobox if present
-        let infobox = infobox_regex.shortest_match(&body);
-        let body = match infobox {
-            Some(end_position) => &body[end_position..],
-            None => &body
-        };
-
-        // Article links are of the form:
-        // [[article name#optional_anchor|display name]]
-        let mut links: Vec<String> = link_regex
-            .captures_iter(body)
-            .map(|x| x
-                .get(1)
-                .unwrap()
-                .as_str()
-                .split("|").next().unwrap()  // Select article name
-                .split("#").next().unwrap()       // Strip in page anchor
-                .trim()
-                .to_string()
-                .capitalize_first_letter())
-            .collect();
-
-        for capture in main_article_regex.captures_iter(&body) {
-            let link = capture
-                .get(1)
-                .unwrap()
-                .as_str()
-                .split("#").next().unwrap()       // Strip in page anchor
-                .trim()
-                .to_string();
-            links.push(link.to_string());
-        }
-
-        for capture in see_also_regex.captures_iter(&body) {
-            for link in capture.get(1).unwrap().as_str().split("|") {
-                links.push(link.split("#").next().unwrap().trim().to_string());
-            }
-        }
+    let (redirects_map, redirect_report) = resolve_redirects(&article_map, &redirect_to);
+    if let Some(redirect_report_path) = redirect_report_path {
+        write_redirect_report(&redirect_report_path, &redirect_report)?;
+    }
 
+    let resolve_links = | links: &mut Vec<&str> | -> Vec<u32> {
         // Remove duplicate elements
         // May be many links to/from the same page
         links.sort_unstable();
         links.dedup();
 
-        // Add the incoming links to any destination pages
-        for link_title in links {
-
-            let dest_article_index = article_map
-                .get(&link_title)
+        let mut dest_article_indices: Vec<u32> = links
+            .iter()
+            .filter_map(|link_title| article_map
+                .get(*link_title)
                 .or(redirects_map
-                    .get(&link_title));
-
-            match dest_article_index {
-                Some(dest_article_index) => {
-                    match &mode {
-                        ParserMode::IncomingLinks => {
-                            articles[*dest_article_index as usize].links.push(*source_article_index);
-                        },
-                        ParserMode::OutgoingLinks => {
-                            articles[*source_article_index as usize].links.push(*dest_article_index);
-                        }
-                    }
+                    .get(*link_title))
+                .copied())
+            .collect();
+        ParseStats::add(&stats.links_found, links.len() as u64);
+        ParseStats::add(&stats.links_unresolved, (links.len() - dest_article_indices.len()) as u64);
+
+        // Different titles can redirect to the same article
+        dest_article_indices.sort_unstable();
+        dest_article_indices.dedup();
+        return dest_article_indices;
+    };
+
+    let add_links = | articles: &mut Vec<Article>, source_article_index: u32, dest_article_indices: Vec<u32> | -> () {
+        // Add the incoming links to any destination pages
+        for dest_article_index in dest_article_indices {
+            match &mode {
+                ParserMode::IncomingLinks => {
+                    articles[dest_article_index as usize].links.push(source_article_index);
                 },
-                None => ()
+                ParserMode::OutgoingLinks => {
+                    articles[source_article_index as usize].links.push(dest_article_index);
+                }
             }
         }
     };
 
-    scan_pages(xml_path, add_links);
+    match link_store {
+        Some(link_store) => {
+            link_store.for_each(| source_article_index, mut links | {
+                let dest_article_indices = resolve_links(&mut links);
+                add_links(&mut articles, source_article_index, dest_article_indices);
+            }).map_err(|e| ParseError::io(&spill_path, e))?;
+        },
+        None => {
+            let find_links = | page: Page | -> Option<(u32, Vec<u32>)> {
+                let source_article_index = article_map.get(&page.title)?;
+                let links = extractor.links(&page.body);
+                let mut links: Vec<&str> = links.iter().map(|x| x.as_str()).collect();
+                return Some((*source_article_index, resolve_links(&mut links)));
+            };
+
+            // The same pages are skipped again so the errors were already reported by the first pass
+            progress.start_pass("pass 2/2");
+            for_each_page(
+                xml_path,
+                streams.as_deref(),
+                &filter,
+                find_links,
+                | (source_article_index, dest_article_indices) | {
+                    add_links(&mut articles, source_article_index, dest_article_indices);
+                }
+            )?;
+            progress.finish_pass();
+        }
+    }
 
-    return (article_map, articles)
+    if report_progress {
+        progress.print_summary();
+    }
+    if let Some(summary_json_path) = summary_json_path {
+        write(&summary_json_path, progress.summary_json() + "\n")
+            .map_err(|e| ParseError::io(&summary_json_path, e))?;
+    }
+    return Ok((article_map, articles, redirects_map));
 }
 
 /// Takes in the values returned by `parse_xml_to_tsv()` and writes them to a TSV file.
 ///
 /// The TSV format produced consists of only a unique sequential integer index
 /// for each article, the article name and then a list of article indices with a link to this article.
+/// The file starts with the metadata of the dataset, one `#key \t value` line for each line of
+/// `Metadata::to_lines()`.
 ///
 /// # Arguments
 /// * `output_path` - File path to write the TSV output to
+/// * `metadata` - Metadata of the dataset, the article and link counts are filled in
 /// * `article_map` - Hashmap of article name -> article index
 /// * `articles` - Adjacency list representation of links graph
 ///
+/// # Errors
+/// Returns an error if the article indices are not contiguous (see `title_table()`)
+/// or the file cannot be written
+///
 pub fn write_to_tsv(
     output_path: &String,
+    metadata: &Metadata,
     article_map: &mut HashMap<String, u32>,
-    articles: &mut Vec<Article>) -> () {
+    articles: &mut [Article]) -> ParseResult<()> {
 
-    assert_eq!(article_map.len(), articles.len());
+    let article_titles = title_table(article_map, articles.len(), output_path)?;
 
-    // Convert hashmap to vec in correct order based on index
-    let mut article_titles: Vec<Option<String>> = Vec::with_capacity(NUM_ARTICLES as usize);
-    for _ in 0..article_map.len() {
-        article_titles.push(None);
+    // Some duplicates may remain after the remap table
+    for article in articles.iter_mut() {
+        article.links.sort_unstable();
+        article.links.dedup();
     }
 
-    for (title, index) in article_map.iter() {
-        article_titles[*index as usize] = Some(title.clone());
-    }
+    let mut metadata = metadata.clone();
+    metadata.article_count = articles.len() as u64;
+    metadata.link_count = articles.iter().map(|x| x.links.len() as u64).sum();
 
-    let mut fout_links_graph = File::create(output_path).unwrap();
+    let mut fout_links_graph = File::create(output_path).map_err(|e| ParseError::io(output_path, e))?;
+    for line in metadata.to_lines() {
+        fout_links_graph
+            .write_all(format!("#{}\n", line).as_bytes())
+            .map_err(|e| ParseError::io(output_path, e))?;
+    }
 
     for article_index in 0..articles.len() {
-        let article_name = article_titles[article_index]
-            .as_ref()
-            .expect("Title index defined");
-
-        // Some duplicates may remain after the remap table
-        articles[article_index].links.sort_unstable();
-        articles[article_index].links.dedup();
+        let article_name = article_titles[article_index];
 
         let links_string: String = articles[article_index].links
             .iter()
@@ -448,12 +1137,139 @@ pub fn write_to_tsv(
             .join("\t");
 
         fout_links_graph
-            .write(format!("{}\t{}\t{}\n",
+            .write_all(format!("{}\t{}\t{}\n",
                            article_index,
                            article_name,
                            links_string).as_bytes())
-            .unwrap();
+            .map_err(|e| ParseError::io(output_path, e))?;
+    }
+    return Ok(());
+}
+
+/// Builds the dense table of article titles in index order from a hashmap of article name -> article index.
+///
+/// # Arguments
+/// * `article_map` - Hashmap of article name -> article index
+/// * `article_count` - Number of articles, the indices must be exactly 0 to `article_count - 1`
+/// * `path` - Path of the dataset, used in errors
+///
+/// # Returns
+/// The title of each article, indexed by article index
+///
+/// # Errors
+/// Returns an error naming an index that is out of range, shared by two titles or missing
+///
+pub fn title_table<'a>(
+    article_map: &'a HashMap<String, u32>,
+    article_count: usize,
+    path: &str) -> ParseResult<Vec<&'a str>> {
+
+    let mut titles: Vec<Option<&str>> = vec![None; article_count];
+    for (title, index) in article_map.iter() {
+        match titles.get_mut(*index as usize) {
+            Some(Some(other_title)) => return Err(ParseError::format(path, format!(
+                "Article indices are not contiguous: '{}' and '{}' both have index {}", other_title, title, index))),
+            Some(entry) => *entry = Some(title),
+            None => return Err(ParseError::format(path, format!(
+                "Article indices are not contiguous: '{}' has index {} but there are {} articles",
+                title, index, article_count)))
+        }
+    }
+    return titles
+        .iter()
+        .enumerate()
+        .map(|(index, title)| title.ok_or_else(|| ParseError::format(path, format!(
+            "Article indices are not contiguous: no article has index {}", index))))
+        .collect();
+}
+
+/// Gets the path of the redirects file stored alongside a dataset, see `write_redirects_to_tsv()`.
+pub fn redirects_path(dataset_path: &str) -> String {
+    return format!("{}.redirects.tsv", dataset_path);
+}
+
+/// Writes the resolved redirects returned by `parse_xml_dump()` to a TSV file alongside the dataset.
+///
+/// The file is written to `redirects_path(dataset_path)` and consists of one line per redirect,
+/// of the redirect name and the index of the article it resolves to. Lines are sorted by redirect name.
+///
+/// # Arguments
+/// * `dataset_path` - Path of the dataset the redirects belong to (eg the output of `write_to_tsv()`)
+/// * `redirects_map` - Hashmap of redirect name -> article index
+///
+/// # Errors
+/// Returns an error if the redirects file cannot be written
+///
+pub fn write_redirects_to_tsv(dataset_path: &str, redirects_map: &HashMap<String, u32>) -> ParseResult<()> {
+    let mut redirects: Vec<(&String, &u32)> = redirects_map.iter().collect();
+    redirects.sort_unstable();
+
+    let path = redirects_path(dataset_path);
+    let file = File::create(&path).map_err(|e| ParseError::io(&path, e))?;
+    let mut fout_redirects = BufWriter::new(file);
+    let write = || -> Result<()> {
+        for (redirect_name, article_index) in redirects {
+            writeln!(fout_redirects, "{}\t{}", redirect_name, article_index)?;
+        }
+        return fout_redirects.flush();
+    };
+    return write().map_err(|e| ParseError::io(&path, e));
+}
+
+/// Loads the redirects stored alongside a dataset by `write_redirects_to_tsv()`.
+///
+/// # Arguments
+/// * `dataset_path` - Path of the dataset the redirects belong to
+///
+/// # Returns
+/// A HashMap of redirect name -> article index, or None if the dataset has no redirects file
+///
+pub fn load_redirects_from_tsv(dataset_path: &str) -> ParseResult<Option<HashMap<String, u32>>> {
+    let path = redirects_path(dataset_path);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ParseError::io(&path, e))
+    };
+    let reader = BufReader::new(file);
+
+    let mut redirects_map: HashMap<String, u32> = HashMap::new();
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| ParseError::line(&path, line_index + 1, e))?;
+        // Redirect name \t article index
+        if let Some(position) = line.rfind('\t') {
+            let article_index = line[position + 1..]
+                .parse::<u32>()
+                .map_err(|_| ParseError::line(&path, line_index + 1, "Invalid article index"))?;
+            redirects_map.insert(line[..position].to_string(), article_index);
+        }
+    }
+    return Ok(Some(redirects_map));
+}
+
+/// Reads the metadata at the start of a TSV dataset (see `write_to_tsv()`).
+///
+/// # Arguments
+/// * `tsv_path` - Path to the TSV file
+///
+/// # Returns
+/// The metadata, or None if the dataset was written before metadata was recorded
+///
+pub fn read_tsv_metadata(tsv_path: &str) -> ParseResult<Option<Metadata>> {
+    let file = File::open(tsv_path).map_err(|e| ParseError::io(tsv_path, e))?;
+    let mut lines: Vec<String> = Vec::new();
+    for (line_index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| ParseError::line(tsv_path, line_index + 1, e))?;
+        match line.strip_prefix('#') {
+            Some(line) => lines.push(line.to_string()),
+            None => break
+        }
     }
+    if lines.len() == 0 {
+        return Ok(None);
+    }
+    let metadata = Metadata::from_lines(lines.iter().enumerate().map(|(i, x)| (i + 1, x.as_str())), tsv_path)?;
+    return Ok(Some(metadata));
 }
 
 /// Loads a TSV (produced by `write_to_tsv()`) back into hashmap and adjacency list representation.
@@ -465,41 +1281,63 @@ pub fn write_to_tsv(
 ///  * A HashMap of article name -> article index
 ///  * An adjacency list representation of the links to each article (may be incoming or outgoing
 ///    depending on how the source tsv file was generated using `parse_xml_dump()`).
+///  * A HashMap of redirect name -> article index, loaded from the redirects file alongside the TSV
+///    (see `write_redirects_to_tsv()`). Empty if the dataset has no redirects file.
 ///
-/// # Panics
-/// May panic if the TSV file becomes corrupted
+/// # Errors
+/// Returns an error with the line number if the TSV file is corrupted, or an error if the
+/// article or link counts do not match the metadata at the start of the file
 ///
-pub fn load_from_tsv(tsv_path: &String) -> (HashMap<String, u32>, Vec<Article>) {
-    let file = File::open(tsv_path).unwrap();
+pub fn load_from_tsv(tsv_path: &String) -> ParseResult<Dataset> {
+    let file = File::open(tsv_path).map_err(|e| ParseError::io(tsv_path, e))?;
     let reader = BufReader::new(file);
 
     let mut lookup_table: HashMap<String, u32> = HashMap::with_capacity(NUM_ARTICLES as usize);
     let mut adjacency_list: Vec<Article> = Vec::with_capacity(NUM_ARTICLES as usize);
+    let mut metadata_lines: Vec<(usize, String)> = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.map_err(|e| ParseError::line(tsv_path, line_number, e))?;
 
-    for line in reader.lines() {
-        let line = line.unwrap();
+        // Metadata lines come before the first article
+        if adjacency_list.len() == 0 {
+            if let Some(metadata_line) = line.strip_prefix('#') {
+                metadata_lines.push((line_number, metadata_line.to_string()));
+                continue;
+            }
+        }
         let fields: Vec<&str> = line.split("\t").collect();
 
         // TSV has at least 2 fields:
         // Index \t Article name \t link indices
         if fields.len() >= 2 {
-            let article_index = fields[0].parse::<u32>().unwrap();
+            let article_index = fields[0]
+                .parse::<u32>()
+                .map_err(|_| ParseError::line(tsv_path, line_number, format!("Invalid article index '{}'", fields[0])))?;
             let article_title = fields[1].to_string();
 
             // There should not be duplicate articles in the TSV
-            assert_eq!(lookup_table.insert(article_title, article_index), None);
+            if lookup_table.contains_key(&article_title) {
+                return Err(ParseError::line(tsv_path, line_number, format!("Duplicate article '{}'", article_title)));
+            }
+            lookup_table.insert(article_title, article_index);
 
             // Index should match line number (0-indexed)
             // If they do not match then we have skipped data
             // and the adjacency list indexes will be wrong
-            assert_eq!(adjacency_list.len(), article_index as usize);
+            if adjacency_list.len() != article_index as usize {
+                return Err(ParseError::line(tsv_path, line_number, format!(
+                    "Expected article index {} but found {}", adjacency_list.len(), article_index)));
+            }
 
             // Collecting sets the vector capacity to the same size as the number of items.
-            let links = match fields[2].len() > 0 {
+            let links = match fields.len() > 2 && fields[2].len() > 0 {
                 true => fields[2..]
                         .iter()
-                        .map(|x| x.parse::<u32>().unwrap())
-                        .collect(),
+                        .map(|x| x.parse::<u32>())
+                        .collect::<std::result::Result<Vec<u32>, _>>()
+                        .map_err(|_| ParseError::line(tsv_path, line_number, "Invalid link index"))?,
                 false => Vec::new()
             };
 
@@ -508,13 +1346,135 @@ pub fn load_from_tsv(tsv_path: &String) -> (HashMap<String, u32>, Vec<Article>)
             });
         }
     }
-    return (lookup_table, adjacency_list);
+
+    if metadata_lines.len() > 0 {
+        let metadata = Metadata::from_lines(metadata_lines.iter().map(|(i, x)| (*i, x.as_str())), tsv_path)?;
+        let link_count: u64 = adjacency_list.iter().map(|x| x.links.len() as u64).sum();
+        metadata.validate(adjacency_list.len() as u64, link_count, tsv_path)?;
+    }
+    let redirects_map = load_redirects_from_tsv(tsv_path)?.unwrap_or_default();
+    return Ok((lookup_table, adjacency_list, redirects_map));
+}
+
+/// Formats a parsed dataset can be stored in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatasetFormat {
+    /// Text format written by `write_to_tsv()`, with redirects in a separate file
+    Tsv,
+    /// Binary compressed sparse row format written by `binary::write_binary()`
+    Binary
+}
+
+impl DatasetFormat {
+    /// Chooses the format to write a dataset in from its path:
+    /// TSV if the file extension is `.tsv`, otherwise binary.
+    pub fn from_path(path: &str) -> DatasetFormat {
+        return match Path::new(path).extension().and_then(|x| x.to_str()) {
+            Some("tsv") => DatasetFormat::Tsv,
+            _ => DatasetFormat::Binary
+        };
+    }
+}
+
+/// Writes a dataset in the given format, see `write_to_tsv()` and `binary::write_binary()`.
+///
+/// Binary datasets hold the links in both directions, TSV datasets only hold the links as given.
+///
+/// # Arguments
+/// * `output_path` - File path to write the dataset to
+/// * `format` - Format to write
+/// * `metadata` - Metadata of the dataset, including the direction of the links in `articles`
+/// * `article_map` - Hashmap of article name -> article index
+/// * `articles` - Adjacency list representation of links graph
+/// * `redirects_map` - Hashmap of redirect name -> article index
+///
+pub fn write_dataset(
+    output_path: &String,
+    format: DatasetFormat,
+    metadata: &Metadata,
+    article_map: &mut HashMap<String, u32>,
+    articles: &mut [Article],
+    redirects_map: &HashMap<String, u32>) -> ParseResult<()> {
+    match format {
+        DatasetFormat::Tsv => {
+            write_to_tsv(output_path, metadata, article_map, articles)?;
+            return write_redirects_to_tsv(output_path, redirects_map);
+        },
+        DatasetFormat::Binary => return binary::write_binary(output_path, metadata, article_map, articles, redirects_map)
+    }
+}
+
+/// Loads a dataset written by `write_dataset()`, detecting whether it is binary or TSV.
+///
+/// # Arguments
+/// * `path` - Path to the dataset
+/// * `mode` - Direction of the links to load. TSV datasets without metadata only hold one
+///   direction and do not record it, which is assumed to be this direction
+///
+pub fn load_dataset(path: &String, mode: ParserMode) -> ParseResult<Dataset> {
+    if binary::is_binary(path)? {
+        return binary::load_binary(path, mode);
+    }
+    return load_from_tsv(path);
+}
+
+/// Parses a dump and writes the dataset, with its metadata, in the format chosen by the output path.
+///
+/// # Arguments
+/// * `xml_path` - Path to the unprocessed XML database dump
+/// * `output_path` - File path to write the dataset to, see `DatasetFormat::from_path()`
+/// * `options` - Options controlling how the dump is parsed
+///
+/// # Errors
+/// Returns an error if the dump cannot be parsed or the dataset cannot be written
+///
+pub fn parse_to_dataset(xml_path: &str, output_path: &str, options: ParseOptions) -> ParseResult<()> {
+    let metadata = Metadata::for_dump(xml_path, options.mode, options.articles_to_ignore.as_ref())?;
+    let (mut article_map, mut articles, redirects_map) = parse_xml_dump(&xml_path.to_string(), options)?;
+    let format = DatasetFormat::from_path(output_path);
+    return write_dataset(&output_path.to_string(), format, &metadata, &mut article_map, &mut articles, &redirects_map);
+}
+
+/// Converts a dataset to the format chosen by the output path, keeping its metadata.
+///
+/// # Arguments
+/// * `input_path` - Path to the dataset to convert, binary or TSV
+/// * `output_path` - File path to write the converted dataset to, see `DatasetFormat::from_path()`
+/// * `mode` - Direction of the links to write to TSV datasets, and of the links in TSV datasets
+///   that do not record it (see `load_metadata()`)
+///
+pub fn convert_dataset(input_path: &str, output_path: &str, mode: ParserMode) -> ParseResult<()> {
+    let metadata = load_metadata(input_path, mode)?;
+    let (mut article_map, mut articles, redirects_map) = load_dataset(&input_path.to_string(), metadata.mode)?;
+    let format = DatasetFormat::from_path(output_path);
+    return write_dataset(&output_path.to_string(), format, &metadata, &mut article_map, &mut articles, &redirects_map);
 }
 
-/// Recursively resolves redirected article links to find the actual article they link to.
+/// Loads the metadata of a dataset, see `metadata::Metadata`.
+///
+/// # Arguments
+/// * `path` - Path to the dataset
+/// * `mode` - Direction of the links to load, as for `load_dataset()`
+///
+/// # Returns
+/// The metadata, with the mode set to the direction of the links `load_dataset()` loads.
+/// TSV datasets record their direction so it is used instead of `mode` when present.
+///
+pub fn load_metadata(path: &str, mode: ParserMode) -> ParseResult<Metadata> {
+    if binary::is_binary(path)? {
+        let mut metadata = LinkGraph::open(path, mode)?.metadata().clone();
+        metadata.mode = mode;
+        return Ok(metadata);
+    }
+    return Ok(read_tsv_metadata(path)?.unwrap_or_else(|| Metadata::new(mode)));
+}
+
+/// Resolves redirected article links to find the actual article they link to.
 ///
 /// Most redirects are only a single step, however there is a small number that
-/// take multiple steps. Some redirect links may not resolve to an actual article and are discarded.
+/// take multiple steps. Each redirect chain is followed until it reaches an article.
+/// Chains that reach a page that does not exist (broken) or loop back on themselves (cyclic)
+/// do not resolve to an actual article and are discarded.
 ///
 /// # Arguments
 /// * `article_map` - Hashmap of article name -> article index
@@ -522,52 +1482,401 @@ pub fn load_from_tsv(tsv_path: &String) -> (HashMap<String, u32>, Vec<Article>)
 ///
 /// # Returns
 /// * A HashMap of article name -> article index, mapping redirected articles to indices
+/// * A report of the redirects that could not be resolved, sorted by redirect name
 ///
 fn resolve_redirects(
     article_map: &HashMap<String, u32>,
-    redirects: &mut HashMap<String, String>) -> HashMap<String, u32> {
+    redirects: &HashMap<String, String>) -> (HashMap<String, u32>, RedirectReport) {
 
-    let mut redirects_map: HashMap<String, u32> = HashMap::with_capacity(NUM_ARTICLES as usize);
+    let mut redirects_map: HashMap<String, u32> = HashMap::with_capacity(redirects.len());
+    let mut report = RedirectReport::default();
 
     for (curr_article_name, redirected_to_article_name) in redirects.iter() {
+        // Names visited so far, chains are almost always one or two steps long
+        // so a linear search for cycles is cheaper than a set
+        let mut chain: Vec<&String> = vec![redirected_to_article_name];
         let mut current_redirect_article_name = redirected_to_article_name;
-        while article_map.get(current_redirect_article_name) == None {
-            if let Some(next_redirect) = redirects.get(current_redirect_article_name) {
-                current_redirect_article_name = next_redirect;
-            }
-            else {
-                // Found a dead link
-                // No matching redirect and no matching article
+        loop {
+            if let Some(redirect_to_index) = article_map.get(current_redirect_article_name) {
+                redirects_map.insert(curr_article_name.clone(), *redirect_to_index);
                 break;
             }
-        }
 
-        if let Some(redirect_to_index) = article_map.get(redirected_to_article_name) {
-            redirects_map.insert(curr_article_name.clone(), *redirect_to_index);
+            match redirects.get(current_redirect_article_name) {
+                Some(next_redirect) => {
+                    let is_cycle = next_redirect == curr_article_name || chain.contains(&next_redirect);
+                    chain.push(next_redirect);
+                    if is_cycle {
+                        report.cyclic.push((curr_article_name.clone(), chain.iter().map(|x| x.to_string()).collect()));
+                        break;
+                    }
+                    current_redirect_article_name = next_redirect;
+                },
+                None => {
+                    // Found a dead link
+                    // No matching redirect and no matching article
+                    report.broken.push((curr_article_name.clone(), chain.iter().map(|x| x.to_string()).collect()));
+                    break;
+                }
+            }
         }
     }
-    return redirects_map;
+
+    report.broken.sort_unstable();
+    report.cyclic.sort_unstable();
+    return (redirects_map, report);
 }
 
-/// Parses the ignore directory: a directory full of text files containing a list of
-/// wikipedia article names that should be ignored. One article per line.
+/// Writes a report of the redirects that could not be resolved to a TSV file.
+///
+/// Each line is the kind of problem (`broken` or `cyclic`), the redirect name and then
+/// the chain of names the redirect passes through.
 ///
 /// # Arguments
-/// * `path` - Path of the directory containing textfiles
+/// * `report_path` - Path of the report file to write
+/// * `report` - Report returned by `resolve_redirects()`
 ///
-/// # Returns
-/// * A HashSet of article names to be ignored
+/// # Errors
+/// Returns an error if the report cannot be written
 ///
-pub fn parse_ignore_directory(path: &String) -> HashSet<String> {
-    let files = read_dir(path).unwrap();
-    let mut to_ignore: HashSet<String> = HashSet::new();
-    for filename in files {
-        let file = File::open(filename.unwrap().path()).unwrap();
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            to_ignore.insert(line.capitalize_first_letter());
+pub fn write_redirect_report(report_path: &str, report: &RedirectReport) -> ParseResult<()> {
+    let file = File::create(report_path).map_err(|e| ParseError::io(report_path, e))?;
+    let mut fout_report = BufWriter::new(file);
+    let entries = report.broken
+        .iter()
+        .map(|x| ("broken", x))
+        .chain(report.cyclic.iter().map(|x| ("cyclic", x)));
+    let write = || -> Result<()> {
+        for (kind, (redirect_name, chain)) in entries {
+            writeln!(fout_report, "{}\t{}\t{}", kind, redirect_name, chain.join("\t"))?;
+        }
+        return fout_report.flush();
+    };
+    return write().map_err(|e| ParseError::io(report_path, e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small dump with a single redirect (USA), a double redirect (US -> USA),
+    /// a redirect cycle (Loop A <-> Loop B) and a broken redirect (Dead end)
+    fn fixture_path() -> String {
+        return format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+    }
+
+    fn parse_fixture(options: ParseOptions) -> Dataset {
+        return parse_xml_dump(&fixture_path(), options).unwrap();
+    }
+
+    /// Asserts parsing a dump gives the same dataset as parsing the fixture with the default options
+    fn assert_parses_like_fixture(xml_path: &str, options: ParseOptions) {
+        let (article_map, articles, redirects_map) = parse_fixture(ParseOptions::default());
+        let (other_article_map, other_articles, other_redirects_map) =
+            parse_xml_dump(&xml_path.to_string(), options).unwrap();
+        assert_eq!(other_article_map, article_map);
+        assert_eq!(other_redirects_map, redirects_map);
+        let links: Vec<&Vec<u32>> = articles.iter().map(|x| &x.links).collect();
+        let other_links: Vec<&Vec<u32>> = other_articles.iter().map(|x| &x.links).collect();
+        assert_eq!(other_links, links);
+    }
+
+    /// Path in the temporary directory that is removed when dropped, even if the test fails first
+    struct TempFile {
+        path: PathBuf
+    }
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            return TempFile { path: std::env::temp_dir().join(format!("parse-{}-{}", std::process::id(), name)) };
+        }
+
+        fn path_string(&self) -> String {
+            return self.path.to_string_lossy().to_string();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn resolves_single_redirect() {
+        let (article_map, _, redirects_map) = parse_fixture(ParseOptions::default());
+        assert_eq!(redirects_map.get("USA"), article_map.get("United States"));
+    }
+
+    #[test]
+    fn resolves_double_redirect() {
+        let (article_map, _, redirects_map) = parse_fixture(ParseOptions::default());
+        assert_eq!(redirects_map.get("US"), article_map.get("United States"));
+    }
+
+    #[test]
+    fn drops_cyclic_and_broken_redirects() {
+        let (_, _, redirects_map) = parse_fixture(ParseOptions::default());
+        assert_eq!(redirects_map.len(), 2);
+        assert!(!redirects_map.contains_key("Loop A"));
+        assert!(!redirects_map.contains_key("Loop B"));
+        assert!(!redirects_map.contains_key("Dead end"));
+    }
+
+    #[test]
+    fn links_follow_redirect_chains() {
+        let options = ParseOptions {
+            mode: ParserMode::OutgoingLinks,
+            ..ParseOptions::default()
+        };
+        let (article_map, articles, _) = parse_fixture(options);
+        let alpha = article_map["Alpha"] as usize;
+        let united_states = article_map["United States"];
+        // USA and US both resolve to United States so give a single link,
+        // the cyclic and broken links are dropped
+        assert_eq!(articles[alpha].links, vec![united_states]);
+    }
+
+    #[test]
+    fn reports_cyclic_and_broken_redirects() {
+        let mut redirects: HashMap<String, String> = HashMap::new();
+        for (name, target) in [
+            ("USA", "United States"),
+            ("US", "USA"),
+            ("Loop A", "Loop B"),
+            ("Loop B", "Loop A"),
+            ("Self", "Self"),
+            ("Dead end", "Missing page")].iter() {
+            redirects.insert(name.to_string(), target.to_string());
+        }
+        let mut article_map: HashMap<String, u32> = HashMap::new();
+        article_map.insert("United States".to_string(), 0);
+
+        let (redirects_map, report) = resolve_redirects(&article_map, &redirects);
+        assert_eq!(redirects_map.len(), 2);
+        assert_eq!(report.broken, vec![
+            ("Dead end".to_string(), vec!["Missing page".to_string()])
+        ]);
+        assert_eq!(report.cyclic, vec![
+            ("Loop A".to_string(), vec!["Loop B".to_string(), "Loop A".to_string()]),
+            ("Loop B".to_string(), vec!["Loop A".to_string(), "Loop B".to_string()]),
+            ("Self".to_string(), vec!["Self".to_string(), "Self".to_string()])
+        ]);
+    }
+
+    #[test]
+    fn writes_redirect_report() {
+        let report_file = TempFile::new("redirect-report.tsv");
+        let options = ParseOptions {
+            redirect_report_path: Some(report_file.path_string()),
+            ..ParseOptions::default()
+        };
+        parse_fixture(options);
+
+        let report = std::fs::read_to_string(&report_file.path).unwrap();
+        assert_eq!(report, "broken\tDead end\tMissing page\n\
+                            cyclic\tLoop A\tLoop B\tLoop A\n\
+                            cyclic\tLoop B\tLoop A\tLoop B\n");
+    }
+
+    #[test]
+    fn parses_compressed_dumps() {
+        for extension in ["bz2", "gz"].iter() {
+            assert_parses_like_fixture(&format!("{}.{}", fixture_path(), extension), ParseOptions::default());
+        }
+    }
+
+    #[test]
+    fn parses_multistream_dumps_with_index() {
+        let fixtures = format!("{}/tests/fixtures", env!("CARGO_MANIFEST_DIR"));
+        let options = ParseOptions {
+            index_path: Some(format!("{}/redirects-multistream-index.txt.bz2", fixtures)),
+            ..ParseOptions::default()
+        };
+        assert_parses_like_fixture(&format!("{}/redirects-multistream.xml.bz2", fixtures), options);
+    }
+
+    #[test]
+    fn single_pass_matches_two_passes() {
+        let spill_file = TempFile::new("single-pass-spill.bin");
+        for pass_mode in [PassMode::SinglePass, PassMode::SinglePassSpill(spill_file.path.clone())] {
+            let options = ParseOptions {
+                pass_mode,
+                ..ParseOptions::default()
+            };
+            assert_parses_like_fixture(&fixture_path(), options);
+        }
+        assert!(!spill_file.path.exists());
+    }
+
+    #[test]
+    fn unwritable_redirect_report_is_an_error() {
+        let report_path = std::env::temp_dir()
+            .join(format!("missing-dir-{}", std::process::id()))
+            .join("redirect-report.tsv")
+            .to_string_lossy()
+            .to_string();
+        let options = ParseOptions {
+            redirect_report_path: Some(report_path),
+            ..ParseOptions::default()
+        };
+        match parse_xml_dump(&fixture_path(), options) {
+            Err(ParseError::Io { .. }) => (),
+            result => panic!("Expected an IO error, got {:?}", result.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn non_contiguous_indices_are_an_error() {
+        let articles = vec!["Alpha", "Beta", "Gamma"];
+        let mut article_map: HashMap<String, u32> = articles
+            .iter()
+            .enumerate()
+            .map(|(index, title)| (title.to_string(), index as u32))
+            .collect();
+        assert_eq!(title_table(&article_map, 3, "test").unwrap(), articles);
+
+        article_map.insert(String::from("Gamma"), 0);
+        assert!(title_table(&article_map, 3, "test").is_err());
+        article_map.insert(String::from("Gamma"), 3);
+        assert!(title_table(&article_map, 3, "test").is_err());
+    }
+
+    #[test]
+    fn tsv_metadata_is_validated() {
+        let file = TempFile::new("metadata.tsv");
+        let path = file.path_string();
+        let (mut article_map, mut articles, _) = parse_fixture(ParseOptions::default());
+        let metadata = Metadata::for_dump(&fixture_path(), ParserMode::OutgoingLinks, None).unwrap();
+        write_to_tsv(&path, &metadata, &mut article_map, &mut articles).unwrap();
+
+        let written = read_tsv_metadata(&path).unwrap().unwrap();
+        assert_eq!(written.mode, ParserMode::OutgoingLinks);
+        assert_eq!(written.article_count, articles.len() as u64);
+        assert_eq!(written.source_dump.as_deref(), Some("redirects.xml"));
+        assert!(load_from_tsv(&path).is_ok());
+
+        // Dropping the last article no longer matches the recorded article count
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let truncated: Vec<&str> = contents.lines().collect();
+        std::fs::write(&path, truncated[..truncated.len() - 1].join("\n")).unwrap();
+        assert!(load_from_tsv(&path).is_err());
+    }
+
+    /// Dump with a page with an invalid namespace (Beta) and a page with an unknown entity (Gamma)
+    fn malformed_fixture_path() -> String {
+        return format!("{}/tests/fixtures/malformed.xml", env!("CARGO_MANIFEST_DIR"));
+    }
+
+    #[test]
+    fn malformed_page_is_an_error() {
+        match parse_xml_dump(&malformed_fixture_path(), ParseOptions::default()) {
+            Err(ParseError::Page { title, .. }) => assert_eq!(title, Some("Beta".to_string())),
+            result => panic!("Expected a page error, got {:?}", result.map(|x| x.0))
         }
     }
-    return to_ignore;
-}
\ No newline at end of file
+
+    fn namespaces_fixture_path() -> String {
+        return format!("{}/tests/fixtures/namespaces.xml", env!("CARGO_MANIFEST_DIR"));
+    }
+
+    /// Parses the namespaces fixture with outgoing links and returns the sorted titles of
+    /// the articles and of the outgoing links of "Alpha"
+    fn parse_namespaces_fixture(namespaces: &[i32]) -> (Vec<String>, Vec<String>) {
+        let options = ParseOptions {
+            mode: ParserMode::OutgoingLinks,
+            namespaces: namespaces.iter().copied().collect(),
+            ..ParseOptions::default()
+        };
+        let (article_map, articles, _) = parse_xml_dump(&namespaces_fixture_path(), options).unwrap();
+        let titles = title_table(&article_map, articles.len(), "test").unwrap();
+
+        let mut article_titles: Vec<String> = titles.iter().map(|x| x.to_string()).collect();
+        article_titles.sort_unstable();
+        let mut link_titles: Vec<String> = articles[article_map["Alpha"] as usize].links
+            .iter()
+            .map(|x| titles[*x as usize].to_string())
+            .collect();
+        link_titles.sort_unstable();
+        return (article_titles, link_titles);
+    }
+
+    #[test]
+    fn drops_pages_outside_the_main_namespace() {
+        let (article_titles, link_titles) = parse_namespaces_fixture(&[0]);
+        assert_eq!(article_titles, vec!["Alpha", "Beta"]);
+        assert_eq!(link_titles, vec!["Beta"]);
+    }
+
+    #[test]
+    fn includes_pages_from_selected_namespaces() {
+        let (article_titles, link_titles) = parse_namespaces_fixture(&[0, 4]);
+        assert_eq!(article_titles, vec!["Alpha", "Beta", "Wikipedia:About"]);
+        assert_eq!(link_titles, vec!["Beta", "Wikipedia:About"]);
+
+        // The category page has no <ns> element so its namespace comes from the title prefix
+        let (article_titles, link_titles) = parse_namespaces_fixture(&[0, 14]);
+        assert_eq!(article_titles, vec!["Alpha", "Beta", "Category:Letters"]);
+        assert_eq!(link_titles, vec!["Beta", "Category:Letters"]);
+    }
+
+    #[test]
+    fn resolves_localized_redirects() {
+        let path = format!("{}/tests/fixtures/weiterleitung.xml", env!("CARGO_MANIFEST_DIR"));
+        let options = ParseOptions {
+            language: LanguageProfile::built_in("de").unwrap(),
+            ..ParseOptions::default()
+        };
+        let (article_map, articles, redirects_map) = parse_xml_dump(&path, options).unwrap();
+        let united_states = article_map["Vereinigte Staaten"];
+        assert!(!article_map.contains_key("USA"));
+        assert_eq!(redirects_map.get("USA"), Some(&united_states));
+        assert_eq!(articles[united_states as usize].links, vec![article_map["Alpha"]]);
+
+        // Without the German profile the redirect is read as an article
+        let (article_map, _, redirects_map) = parse_xml_dump(&path, ParseOptions::default()).unwrap();
+        assert!(article_map.contains_key("USA"));
+        assert_eq!(redirects_map.get("USA"), None);
+    }
+
+    #[test]
+    fn resolves_redirect_elements_and_bodies() {
+        let path = format!("{}/tests/fixtures/redirect-bodies.xml", env!("CARGO_MANIFEST_DIR"));
+        let (article_map, _, redirects_map) = parse_xml_dump(&path, ParseOptions::default()).unwrap();
+        let alpha = article_map["Alpha"];
+        let beta = article_map["Beta"];
+
+        // The <redirect title="..."> element wins over the link in the body
+        assert_eq!(redirects_map.get("Element wins"), Some(&beta));
+        // Magic words are matched case insensitively and the target's first letter is capitalized
+        assert_eq!(redirects_map.get("Lowercase"), Some(&beta));
+        // Leading whitespace is skipped and the anchor and display text are dropped
+        assert_eq!(redirects_map.get("Leading whitespace"), Some(&alpha));
+        // The magic word only counts at the start of the body
+        assert!(article_map.contains_key("Not a redirect"));
+        assert_eq!(redirects_map.get("Not a redirect"), None);
+    }
+
+    #[test]
+    fn failed_single_pass_removes_spill_file() {
+        let spill_file = TempFile::new("failed-spill.bin");
+        let options = ParseOptions {
+            pass_mode: PassMode::SinglePassSpill(spill_file.path.clone()),
+            ..ParseOptions::default()
+        };
+        assert!(parse_xml_dump(&malformed_fixture_path(), options).is_err());
+        assert!(!spill_file.path.exists());
+    }
+
+    #[test]
+    fn skips_malformed_pages() {
+        let options = ParseOptions {
+            skip_bad_pages: true,
+            ..ParseOptions::default()
+        };
+        let (article_map, _, _) = parse_xml_dump(&malformed_fixture_path(), options).unwrap();
+        let mut titles: Vec<&String> = article_map.keys().collect();
+        titles.sort_unstable();
+        assert_eq!(titles, vec!["Alpha", "Delta"]);
+    }
+}
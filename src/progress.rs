@@ -0,0 +1,365 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Minimum time between progress reports
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Counts of the pages and links seen while parsing a dump.
+/// Counters are atomic so they can be updated while pages are scanned in parallel.
+#[derive(Default)]
+pub struct ParseStats {
+    /// Pages read from the dump (in the first pass)
+    pub pages: AtomicU64,
+    /// Valid article pages
+    pub articles: AtomicU64,
+    /// Valid redirect pages
+    pub redirects: AtomicU64,
+    /// Disambiguation pages
+    pub disambiguations: AtomicU64,
+    /// Pages in the articles to ignore
+    pub ignored: AtomicU64,
+    /// Malformed pages skipped
+    pub skipped: AtomicU64,
+    /// Unique links found in articles
+    pub links_found: AtomicU64,
+    /// Links that did not resolve to an article (or a redirect to one) so were dropped
+    pub links_unresolved: AtomicU64
+}
+
+impl ParseStats {
+    /// Adds to a counter
+    pub fn add(counter: &AtomicU64, count: u64) {
+        counter.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Reads a counter
+    pub fn get(counter: &AtomicU64) -> u64 {
+        return counter.load(Ordering::Relaxed);
+    }
+
+    /// Pages that were not used for any other reason, eg pages of other namespaces or with invalid titles
+    pub fn filtered(&self) -> u64 {
+        let used = ParseStats::get(&self.articles)
+            + ParseStats::get(&self.redirects)
+            + ParseStats::get(&self.disambiguations)
+            + ParseStats::get(&self.ignored)
+            + ParseStats::get(&self.skipped);
+        return ParseStats::get(&self.pages).saturating_sub(used);
+    }
+}
+
+/// Timing of a finished pass over the dump
+struct PassSummary {
+    name: String,
+    pages: u64,
+    bytes: u64,
+    duration: Duration
+}
+
+/// State of the pass in progress
+struct PassState {
+    name: String,
+    start: Instant,
+    last_report: Instant
+}
+
+/// Tracks and reports the progress of parsing a dump on stderr.
+///
+/// For each pass over the dump a line is printed every few seconds with the bytes read compared to the
+/// size of the dump, pages per second, page counts and an estimate of the time remaining. Bytes are
+/// counted before decompression so the estimate also works for compressed dumps.
+pub struct Progress {
+    /// Counts of pages and links
+    pub stats: ParseStats,
+    /// Bytes of the dump read in the current pass, see `dump::open_dump_counted()`
+    bytes_read: Arc<AtomicU64>,
+    /// Pages read in the current pass
+    pass_pages: AtomicU64,
+    /// Size of the dump file in bytes
+    total_bytes: u64,
+    /// Whether progress is printed, the stats are always counted
+    enabled: bool,
+    start: Instant,
+    pass: Mutex<PassState>,
+    passes: Mutex<Vec<PassSummary>>
+}
+
+impl Progress {
+
+    /// Creates a progress tracker for a dump.
+    ///
+    /// # Arguments
+    /// * `total_bytes` - Size of the dump file in bytes
+    /// * `enabled` - Whether progress is printed to stderr
+    ///
+    pub fn new(total_bytes: u64, enabled: bool) -> Progress {
+        let now = Instant::now();
+        return Progress {
+            stats: ParseStats::default(),
+            bytes_read: Arc::new(AtomicU64::new(0)),
+            pass_pages: AtomicU64::new(0),
+            total_bytes,
+            enabled,
+            start: now,
+            pass: Mutex::new(PassState {
+                name: String::new(),
+                start: now,
+                last_report: now
+            }),
+            passes: Mutex::new(Vec::new())
+        };
+    }
+
+    /// Counter of the bytes read in the current pass, to be given to the readers of the dump
+    pub fn bytes_read_counter(&self) -> Arc<AtomicU64> {
+        return self.bytes_read.clone();
+    }
+
+    /// Starts a new pass over the dump, resetting the bytes and pages read.
+    pub fn start_pass(&self, name: &str) {
+        self.bytes_read.store(0, Ordering::Relaxed);
+        self.pass_pages.store(0, Ordering::Relaxed);
+        let now = Instant::now();
+        let mut pass = self.pass.lock().unwrap();
+        pass.name = name.to_string();
+        pass.start = now;
+        pass.last_report = now;
+    }
+
+    /// Records that a page has been read in the current pass
+    pub fn page_read(&self) {
+        self.pass_pages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Prints a progress line if enough time has passed since the last one
+    pub fn maybe_report(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut pass = self.pass.lock().unwrap();
+        let now = Instant::now();
+        if now.duration_since(pass.last_report) >= REPORT_INTERVAL {
+            pass.last_report = now;
+            eprintln!("{}", self.progress_line(&pass, now));
+        }
+    }
+
+    /// Finishes the current pass, printing a final progress line for it.
+    /// The page count of the first pass is used as the total page count.
+    pub fn finish_pass(&self) {
+        let pass = self.pass.lock().unwrap();
+        let now = Instant::now();
+        let pages = self.pass_pages.load(Ordering::Relaxed);
+        let mut passes = self.passes.lock().unwrap();
+        if passes.is_empty() {
+            self.stats.pages.store(pages, Ordering::Relaxed);
+        }
+        passes.push(PassSummary {
+            name: pass.name.clone(),
+            pages,
+            bytes: self.bytes_read.load(Ordering::Relaxed),
+            duration: now.duration_since(pass.start)
+        });
+        if self.enabled {
+            eprintln!("{}", self.progress_line(&pass, now));
+        }
+    }
+
+    /// Formats a progress line for the pass in progress
+    fn progress_line(&self, pass: &PassState, now: Instant) -> String {
+        let elapsed = now.duration_since(pass.start).as_secs_f64();
+        let bytes_read = self.bytes_read.load(Ordering::Relaxed);
+        let pages = self.pass_pages.load(Ordering::Relaxed);
+        let pages_per_second = match elapsed > 0.0 {
+            true => pages as f64 / elapsed,
+            false => 0.0
+        };
+        let percent = match self.total_bytes {
+            0 => 100.0,
+            total_bytes => 100.0 * bytes_read as f64 / total_bytes as f64
+        };
+        let eta = match bytes_read {
+            0 => String::from("unknown"),
+            _ => format_duration(Duration::from_secs_f64(
+                elapsed * self.total_bytes.saturating_sub(bytes_read) as f64 / bytes_read as f64))
+        };
+
+        return format!(
+            "[{}] {} / {} ({:.1}%), {} pages ({:.0} pages/s), {} articles, {} redirects, \
+             {} disambiguations, {} ignored, ETA {}",
+            pass.name,
+            format_bytes(bytes_read),
+            format_bytes(self.total_bytes),
+            percent,
+            pages,
+            pages_per_second,
+            ParseStats::get(&self.stats.articles),
+            ParseStats::get(&self.stats.redirects),
+            ParseStats::get(&self.stats.disambiguations),
+            ParseStats::get(&self.stats.ignored),
+            eta
+        );
+    }
+
+    /// Prints the summary of the whole parse to stderr
+    pub fn print_summary(&self) {
+        let stats = &self.stats;
+        eprintln!("Parsed {} pages in {}", ParseStats::get(&stats.pages), format_duration(self.start.elapsed()));
+        for pass in self.passes.lock().unwrap().iter() {
+            eprintln!(
+                "  {}: {} pages, {} in {}",
+                pass.name,
+                pass.pages,
+                format_bytes(pass.bytes),
+                format_duration(pass.duration)
+            );
+        }
+        eprintln!("  Articles: {}", ParseStats::get(&stats.articles));
+        eprintln!("  Redirects: {}", ParseStats::get(&stats.redirects));
+        eprintln!("  Disambiguation pages: {}", ParseStats::get(&stats.disambiguations));
+        eprintln!("  Ignored pages: {}", ParseStats::get(&stats.ignored));
+        eprintln!("  Other pages (namespace or title filtered): {}", stats.filtered());
+        eprintln!("  Malformed pages skipped: {}", ParseStats::get(&stats.skipped));
+        eprintln!("  Links found: {}", ParseStats::get(&stats.links_found));
+        eprintln!("  Links dropped as unresolved: {}", ParseStats::get(&stats.links_unresolved));
+    }
+
+    /// Formats the summary of the whole parse as a JSON object
+    pub fn summary_json(&self) -> String {
+        let stats = &self.stats;
+        let passes: Vec<String> = self.passes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pass| format!(
+                "{{\"name\":{},\"pages\":{},\"bytes\":{},\"seconds\":{:.3}}}",
+                json_string(&pass.name),
+                pass.pages,
+                pass.bytes,
+                pass.duration.as_secs_f64()
+            ))
+            .collect();
+
+        return format!(
+            "{{\"pages\":{},\"articles\":{},\"redirects\":{},\"disambiguations\":{},\"ignored\":{},\
+             \"filtered\":{},\"skipped\":{},\"links_found\":{},\"links_unresolved\":{},\
+             \"dump_bytes\":{},\"seconds\":{:.3},\"passes\":[{}]}}",
+            ParseStats::get(&stats.pages),
+            ParseStats::get(&stats.articles),
+            ParseStats::get(&stats.redirects),
+            ParseStats::get(&stats.disambiguations),
+            ParseStats::get(&stats.ignored),
+            stats.filtered(),
+            ParseStats::get(&stats.skipped),
+            ParseStats::get(&stats.links_found),
+            ParseStats::get(&stats.links_unresolved),
+            self.total_bytes,
+            self.start.elapsed().as_secs_f64(),
+            passes.join(",")
+        );
+    }
+}
+
+/// Formats a string as a JSON string literal, escaping quotes, backslashes and control characters
+fn json_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if (c as u32) < 0x20 => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c)
+        }
+    }
+    literal.push('"');
+    return literal;
+}
+
+/// Formats a number of bytes in human readable units
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    return format!("{:.1} {}", value, UNITS[unit]);
+}
+
+/// Formats a duration as hours:minutes:seconds
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    return format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(0), "0.0 B");
+        assert_eq!(format_bytes(1023), "1023.0 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+        // Larger sizes stay in the largest unit
+        assert_eq!(format_bytes(2048 * 1024 * 1024 * 1024), "2048.0 GiB");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "00:00:00");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "00:00:59");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 25 * 60 + 7)), "03:25:07");
+        assert_eq!(format_duration(Duration::from_secs(100 * 3600)), "100:00:00");
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("pass 1/2"), "\"pass 1/2\"");
+        assert_eq!(json_string("a \"quoted\" C:\\dump"), "\"a \\\"quoted\\\" C:\\\\dump\"");
+        assert_eq!(json_string("tab\tline\n\u{1}"), "\"tab\\tline\\n\\u0001\"");
+
+        let progress = Progress::new(0, false);
+        progress.start_pass("pass \"1\"");
+        progress.finish_pass();
+        assert!(progress.summary_json().contains("\"passes\":[{\"name\":\"pass \\\"1\\\"\","));
+    }
+
+    #[test]
+    fn summary_json_has_every_count() {
+        let progress = Progress::new(4096, false);
+        progress.start_pass("pass 1/2");
+        for _ in 0..10 {
+            progress.page_read();
+        }
+        progress.bytes_read_counter().store(4096, Ordering::Relaxed);
+        progress.finish_pass();
+
+        let stats = &progress.stats;
+        ParseStats::add(&stats.articles, 4);
+        ParseStats::add(&stats.redirects, 2);
+        ParseStats::add(&stats.disambiguations, 1);
+        ParseStats::add(&stats.ignored, 1);
+        ParseStats::add(&stats.links_found, 12);
+        ParseStats::add(&stats.links_unresolved, 3);
+
+        // The times depend on how long the test takes
+        let seconds = Regex::new(r#""seconds":\d+\.\d{3}"#).unwrap();
+        let json = progress.summary_json();
+        assert_eq!(
+            seconds.replace_all(&json, r#""seconds":0"#),
+            "{\"pages\":10,\"articles\":4,\"redirects\":2,\"disambiguations\":1,\"ignored\":1,\
+             \"filtered\":2,\"skipped\":0,\"links_found\":12,\"links_unresolved\":3,\
+             \"dump_bytes\":4096,\"seconds\":0,\
+             \"passes\":[{\"name\":\"pass 1/2\",\"pages\":10,\"bytes\":4096,\"seconds\":0}]}"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs::{File, remove_file};
+use std::io::*;
+use std::mem;
+use std::path::PathBuf;
+
+/// Storage for the raw (unresolved) link targets of each article found during a single pass parse.
+///
+/// Links can only be resolved to article indices once every title and redirect in the dump is
+/// known, so in single pass mode the link targets are held until the end of the pass.
+/// Each article's links are stored as one record of the link targets joined by tabs,
+/// which is much more compact than a `String` per link.
+///
+/// Records are either held in memory or spilled to a temporary file on disk when the dump
+/// is too large for the links to fit in memory.
+pub enum LinkStore {
+    Memory(Vec<(u32, Box<str>)>),
+    Spill {
+        // Declared before the file so it is closed before the file is removed when dropped
+        writer: BufWriter<File>,
+        file: SpillFile
+    }
+}
+
+/// Path of a spill file, the file is removed when this is dropped so it is not left behind
+/// if parsing fails part way through a pass.
+pub struct SpillFile {
+    path: PathBuf
+}
+
+impl SpillFile {
+    /// Removes the file now, returning any error instead of ignoring it as dropping does
+    fn remove(mut self) -> Result<()> {
+        return remove_file(mem::take(&mut self.path));
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = remove_file(&self.path);
+        }
+    }
+}
+
+impl LinkStore {
+
+    /// Creates a link store that holds all links in memory.
+    pub fn in_memory() -> LinkStore {
+        return LinkStore::Memory(Vec::new());
+    }
+
+    /// Creates a link store that spills links to a temporary file.
+    /// The file is created (or truncated) immediately and removed by `for_each()`,
+    /// or when the store is dropped.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the temporary file
+    ///
+    pub fn spill_to(path: PathBuf) -> Result<LinkStore> {
+        let writer = BufWriter::new(File::create(&path)?);
+        return Ok(LinkStore::Spill { writer, file: SpillFile { path } });
+    }
+
+    /// Adds the links of an article to the store.
+    ///
+    /// Link targets containing tabs or newlines can never match a valid title so are dropped.
+    ///
+    /// # Arguments
+    /// * `article_index` - Index of the article the links were found in
+    /// * `links` - Raw link targets found in the article
+    ///
+    pub fn push(&mut self, article_index: u32, links: &[String]) -> Result<()> {
+        let record = links
+            .iter()
+            .filter(|x| !x.contains('\t') && !x.contains('\n'))
+            .map(|x| x.as_str())
+            .collect::<Vec<&str>>()
+            .join("\t");
+
+        match self {
+            LinkStore::Memory(records) => {
+                records.push((article_index, record.into_boxed_str()));
+            },
+            LinkStore::Spill { writer, .. } => {
+                // Record format: article index (u32 LE), record length (u32 LE), record bytes
+                writer.write_all(&article_index.to_le_bytes())?;
+                writer.write_all(&(record.len() as u32).to_le_bytes())?;
+                writer.write_all(record.as_bytes())?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Consumes the store calling the callback with the article index and link targets of
+    /// every record in the order they were added. A spill file is removed once it has been read.
+    ///
+    /// # Arguments
+    /// * `callback` - A callback executed for every record
+    ///
+    pub fn for_each<F>(self, mut callback: F) -> Result<()>
+        where F: FnMut(u32, Vec<&str>) {
+        match self {
+            LinkStore::Memory(records) => {
+                for (article_index, record) in records.iter() {
+                    callback(*article_index, split_record(record));
+                }
+            },
+            LinkStore::Spill { writer, file } => {
+                drop(writer.into_inner().map_err(|e| e.into_error())?);
+
+                let mut reader = BufReader::new(File::open(&file.path)?);
+                let mut header = [0u8; 8];
+                let mut record = Vec::new();
+                loop {
+                    match reader.read_exact(&mut header) {
+                        Ok(()) => (),
+                        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                        Err(e) => return Err(e)
+                    }
+                    let article_index = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+                    let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+                    record.resize(length as usize, 0);
+                    reader.read_exact(&mut record)?;
+                    let record = std::str::from_utf8(&record)
+                        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                    callback(article_index, split_record(record));
+                }
+                drop(reader);
+                file.remove()?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Splits a record back into the link targets it was created from
+fn split_record(record: &str) -> Vec<&str> {
+    if record.is_empty() {
+        return Vec::new();
+    }
+    return record.split('\t').collect();
+}
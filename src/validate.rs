@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::*;
+use memmap2::Mmap;
+use crate::binary::{self, Header, Sections};
+use crate::error::{ParseError, ParseResult};
+use crate::metadata::Metadata;
+use crate::parse;
+
+/// How serious an issue found in a dataset is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The dataset is corrupt, loading it fails or gives wrong results
+    Error,
+    /// The dataset loads correctly but has something unexpected, eg an article linking to itself
+    Warning
+}
+
+/// Where in a dataset an issue was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// The dataset as a whole, eg its header
+    Dataset,
+    /// A line of a TSV file, starting at 1
+    Line(usize),
+    /// An article of a binary dataset, by index
+    Article(u32),
+    /// A redirect of a binary dataset, by position in the sorted redirect table
+    Redirect(usize)
+}
+
+/// An issue found in a dataset by `validate_dataset()`
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Path of the file the issue is in, the dataset or the redirects file of a TSV dataset
+    pub path: String,
+    pub location: Location,
+    pub message: String
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?
+        }
+        match self.location {
+            Location::Dataset => write!(f, "{}: {}", self.path, self.message),
+            Location::Line(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+            Location::Article(article) => write!(f, "{}: article {}: {}", self.path, article, self.message),
+            Location::Redirect(redirect) => write!(f, "{}: redirect {}: {}", self.path, redirect, self.message)
+        }
+    }
+}
+
+/// Issues found in one file of a dataset
+struct Issues<'a> {
+    path: &'a str,
+    issues: &'a mut Vec<Issue>
+}
+
+impl Issues<'_> {
+
+    fn push<M: ToString>(&mut self, severity: Severity, location: Location, message: M) {
+        self.issues.push(Issue {
+            severity,
+            path: self.path.to_string(),
+            location,
+            message: message.to_string()
+        });
+    }
+
+    fn error<M: ToString>(&mut self, location: Location, message: M) {
+        self.push(Severity::Error, location, message);
+    }
+
+    fn warning<M: ToString>(&mut self, location: Location, message: M) {
+        self.push(Severity::Warning, location, message);
+    }
+
+    /// Records an error returned while reading part of the dataset (eg its metadata)
+    fn parse_error(&mut self, error: ParseError) {
+        match error {
+            ParseError::Line { line, message, .. } => self.error(Location::Line(line), message),
+            ParseError::Format { message, .. } => self.error(Location::Dataset, message),
+            error => self.error(Location::Dataset, error)
+        }
+    }
+}
+
+/// Checks a dataset for corruption without loading it, so every issue is found rather than only the first.
+///
+/// The checks are:
+///  * Article indices of a TSV dataset are sequential and every line has a title
+///  * Titles and redirect names are valid UTF-8 and not duplicated
+///  * Links are in range, sorted and not duplicated. Links from an article to itself are a warning,
+///    as they come from articles that link to themselves in the dump.
+///  * The offsets and title order of a binary dataset are consistent
+///  * The reverse links of a binary dataset are the transpose of the links
+///  * Redirects resolve to an article in range
+///  * The article and link counts match the metadata
+///
+/// # Arguments
+/// * `path` - Path to the dataset, binary or TSV
+///
+/// # Returns
+/// The issues found, in the order they appear in the dataset
+///
+/// # Errors
+/// Returns an error only if the dataset cannot be read
+///
+pub fn validate_dataset(path: &str) -> ParseResult<Vec<Issue>> {
+    let mut issues: Vec<Issue> = Vec::new();
+    if binary::is_binary(path)? {
+        validate_binary(path, &mut issues)?;
+    }
+    else {
+        validate_tsv(path, &mut issues)?;
+    }
+    return Ok(issues);
+}
+
+/// Checks the links of each article, see `validate_dataset()`
+///
+/// # Arguments
+/// * `links` - Location, article index and links of each article in index order
+/// * `article_count` - Number of articles in the dataset
+/// * `prefix` - Prefix of the messages, to tell apart the two directions of a binary dataset
+/// * `self_links` - Whether to warn about links from an article to itself, only done for one direction
+///   of a binary dataset as the reverse links have the same ones
+/// * `issues` - Issues to add to
+///
+fn check_links<'a, I>(links: I, article_count: usize, prefix: &str, self_links: bool, issues: &mut Issues)
+    where I: Iterator<Item = (Location, u32, &'a [u32])> {
+
+    for (location, article, article_links) in links {
+        let out_of_range: Vec<&u32> = article_links.iter().filter(|x| **x as usize >= article_count).collect();
+        if out_of_range.len() > 0 {
+            issues.error(location.clone(), format!(
+                "{}{} links out of range (eg {}, there are {} articles)",
+                prefix, out_of_range.len(), out_of_range[0], article_count));
+        }
+        if self_links && article_links.contains(&article) {
+            issues.warning(location.clone(), format!("{}Links to itself", prefix));
+        }
+
+        let duplicates = article_links.windows(2).filter(|x| x[0] == x[1]).count();
+        if duplicates > 0 {
+            issues.error(location.clone(), format!("{}{} duplicate links", prefix, duplicates));
+        }
+        if let Some(position) = article_links.windows(2).position(|x| x[0] > x[1]) {
+            issues.error(location, format!(
+                "{}Links are not sorted ({} comes before {})",
+                prefix, article_links[position], article_links[position + 1]));
+        }
+    }
+}
+
+/// Checks the counts recorded in the metadata of a dataset
+fn check_metadata(metadata: &Metadata, article_count: u64, link_count: u64, issues: &mut Issues) {
+    if let Err(e) = metadata.validate(article_count, link_count, issues.path) {
+        issues.parse_error(e);
+    }
+}
+
+/// A line of a TSV dataset read by `validate_tsv()`
+struct Row {
+    line: usize,
+    /// Article index as written on the line, which may not match the position of the row
+    index: u32,
+    links: Vec<u32>
+}
+
+/// Checks a TSV dataset (see `parse::write_to_tsv()`) and its redirects file
+fn validate_tsv(path: &str, all_issues: &mut Vec<Issue>) -> ParseResult<()> {
+    let reader = BufReader::new(File::open(path).map_err(|e| ParseError::io(path, e))?);
+    let article_count = validate_tsv_rows(reader, path, all_issues)?;
+    return validate_tsv_redirects(path, article_count, all_issues);
+}
+
+/// Checks the rows of a TSV dataset, see `validate_tsv()`
+///
+/// # Arguments
+/// * `reader` - Reader over the contents of the dataset
+/// * `path` - Path of the dataset, used in issues and errors
+/// * `all_issues` - Issues to add to
+///
+/// # Returns
+/// The number of articles in the dataset
+///
+fn validate_tsv_rows<R: BufRead>(mut reader: R, path: &str, all_issues: &mut Vec<Issue>) -> ParseResult<usize> {
+    let mut issues = Issues { path, issues: all_issues };
+
+    let mut rows: Vec<Row> = Vec::new();
+    let mut titles: HashMap<String, usize> = HashMap::new();
+    let mut metadata_lines: Vec<(usize, String)> = Vec::new();
+    let mut previous_index: Option<u32> = None;
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        bytes.clear();
+        if reader.read_until(b'\n', &mut bytes).map_err(|e| ParseError::io(path, e))? == 0 {
+            break;
+        }
+        line_number += 1;
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+        }
+
+        // Keep checking lines with invalid UTF-8 so the issues after them are found too
+        let line = String::from_utf8_lossy(&bytes);
+        if std::str::from_utf8(&bytes).is_err() {
+            issues.error(Location::Line(line_number), "Invalid UTF-8");
+        }
+
+        if rows.len() == 0 {
+            if let Some(metadata_line) = line.strip_prefix('#') {
+                metadata_lines.push((line_number, metadata_line.to_string()));
+                continue;
+            }
+        }
+        if line.len() == 0 {
+            continue;
+        }
+
+        // Index \t Article name \t link indices
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 2 {
+            issues.error(Location::Line(line_number), "Expected an article index and title");
+            continue;
+        }
+        let article_index = match fields[0].parse::<u32>() {
+            Ok(article_index) => article_index,
+            Err(_) => {
+                issues.error(Location::Line(line_number), format!("Invalid article index '{}'", fields[0]));
+                continue;
+            }
+        };
+
+        // Compare with the previous index rather than the line count, so one missing line is one issue
+        let expected_index = previous_index.map(|x| x + 1).unwrap_or(0);
+        if article_index != expected_index {
+            issues.error(Location::Line(line_number), format!(
+                "Expected article index {} but found {}", expected_index, article_index));
+        }
+        previous_index = Some(article_index);
+
+        match titles.get(fields[1]) {
+            Some(first_line) => issues.error(Location::Line(line_number), format!(
+                "Duplicate article '{}' (first on line {})", fields[1], first_line)),
+            None => {
+                titles.insert(fields[1].to_string(), line_number);
+            }
+        }
+
+        let mut links: Vec<u32> = Vec::with_capacity(fields.len() - 2);
+        for field in fields[2..].iter().filter(|x| x.len() > 0) {
+            match field.parse::<u32>() {
+                Ok(link) => links.push(link),
+                Err(_) => issues.error(Location::Line(line_number), format!("Invalid link index '{}'", field))
+            }
+        }
+        rows.push(Row {
+            line: line_number,
+            index: article_index,
+            links
+        });
+    }
+
+    let links = rows.iter().map(|x| (Location::Line(x.line), x.index, &x.links[..]));
+    check_links(links, rows.len(), "", true, &mut issues);
+
+    if metadata_lines.len() > 0 {
+        match Metadata::from_lines(metadata_lines.iter().map(|(i, x)| (*i, x.as_str())), path) {
+            Ok(metadata) => {
+                let link_count: u64 = rows.iter().map(|x| x.links.len() as u64).sum();
+                check_metadata(&metadata, rows.len() as u64, link_count, &mut issues);
+            },
+            Err(e) => issues.parse_error(e)
+        }
+    }
+    return Ok(rows.len());
+}
+
+/// Checks the redirects file of a TSV dataset, see `parse::write_redirects_to_tsv()`
+fn validate_tsv_redirects(dataset_path: &str, article_count: usize, all_issues: &mut Vec<Issue>) -> ParseResult<()> {
+    let path = parse::redirects_path(dataset_path);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(ParseError::io(&path, e))
+    };
+    let mut issues = Issues { path: &path, issues: all_issues };
+    let mut reader = BufReader::new(file);
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        bytes.clear();
+        if reader.read_until(b'\n', &mut bytes).map_err(|e| ParseError::io(&path, e))? == 0 {
+            break;
+        }
+        line_number += 1;
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+        }
+        let line = String::from_utf8_lossy(&bytes);
+        if std::str::from_utf8(&bytes).is_err() {
+            issues.error(Location::Line(line_number), "Invalid UTF-8");
+        }
+
+        // Redirect name \t article index
+        let (name, article_index) = match line.rfind('\t') {
+            Some(position) => (&line[..position], &line[position + 1..]),
+            None => {
+                issues.error(Location::Line(line_number), "Expected a redirect name and article index");
+                continue;
+            }
+        };
+        match article_index.parse::<u32>() {
+            Ok(article_index) if article_index as usize >= article_count => issues.error(
+                Location::Line(line_number),
+                format!("Redirect '{}' to article {} out of range (there are {} articles)", name, article_index, article_count)),
+            Ok(_) => (),
+            Err(_) => issues.error(Location::Line(line_number), format!("Invalid article index '{}'", article_index))
+        }
+        match names.get(name) {
+            Some(first_line) => issues.error(Location::Line(line_number), format!(
+                "Duplicate redirect '{}' (first on line {})", name, first_line)),
+            None => {
+                names.insert(name.to_string(), line_number);
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Checks offsets start at 0, never decrease and end at the size of the section they index.
+///
+/// # Returns
+/// Whether the offsets are valid, the sections they index are only checked if they are
+///
+fn check_offsets(offsets: &[u64], size: usize, name: &str, issues: &mut Issues) -> bool {
+    if offsets.first() != Some(&0) {
+        issues.error(Location::Dataset, format!("{} do not start at 0", name));
+        return false;
+    }
+    if let Some(position) = offsets.windows(2).position(|x| x[0] > x[1]) {
+        issues.error(Location::Article(position as u32), format!("{} decrease", name));
+        return false;
+    }
+    if offsets.last().map(|x| *x as usize) != Some(size) {
+        issues.error(Location::Dataset, format!("{} do not end at the size of the section ({})", name, size));
+        return false;
+    }
+    return true;
+}
+
+/// Gets the range of an item from its offsets, which must have been checked by `check_offsets()`
+fn item_range(offsets: &[u64], item: usize) -> std::ops::Range<usize> {
+    return offsets[item] as usize..offsets[item + 1] as usize;
+}
+
+/// Checks a binary dataset (see `binary::Header`)
+fn validate_binary(path: &str, all_issues: &mut Vec<Issue>) -> ParseResult<()> {
+    let mut issues = Issues { path, issues: all_issues };
+    if cfg!(target_endian = "big") {
+        issues.error(Location::Dataset, "Binary datasets can only be used on little endian systems");
+        return Ok(());
+    }
+
+    let file = File::open(path).map_err(|e| ParseError::io(path, e))?;
+    // See `LinkGraph::open()`
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| ParseError::io(path, e))?;
+    let bytes: &[u8] = &mmap;
+
+    let header = match Header::read(bytes, path) {
+        Ok(header) => header,
+        Err(e) => {
+            issues.parse_error(e);
+            return Ok(());
+        }
+    };
+    let sections = match Sections::new(&header, bytes.len() as u64, path) {
+        Ok(sections) => sections,
+        Err(e) => {
+            issues.parse_error(e);
+            return Ok(());
+        }
+    };
+    let article_count = header.article_count as usize;
+    let u64s = |range: &std::ops::Range<usize>| -> &[u64] { bytemuck::cast_slice(&bytes[range.clone()]) };
+    let u32s = |range: &std::ops::Range<usize>| -> &[u32] { bytemuck::cast_slice(&bytes[range.clone()]) };
+
+    // Links in both directions
+    let offsets = u64s(&sections.offsets);
+    let neighbors = u32s(&sections.neighbors);
+    let links_valid = check_offsets(offsets, neighbors.len(), "Link offsets", &mut issues);
+    if links_valid {
+        let links = (0..article_count).map(|x| (Location::Article(x as u32), x as u32, &neighbors[item_range(offsets, x)]));
+        check_links(links, article_count, "", true, &mut issues);
+    }
+
+    let reverse_offsets = u64s(&sections.reverse_offsets);
+    let reverse_neighbors = u32s(&sections.reverse_neighbors);
+    if check_offsets(reverse_offsets, reverse_neighbors.len(), "Reverse link offsets", &mut issues) {
+        let reverse_links = |x: usize| &reverse_neighbors[item_range(reverse_offsets, x)];
+        let links = (0..article_count).map(|x| (Location::Article(x as u32), x as u32, reverse_links(x)));
+        check_links(links, article_count, "Reverse links: ", false, &mut issues);
+
+        // Links out of range were reported above and cannot be transposed
+        let links = (0..article_count).map(|x| &neighbors[item_range(offsets, x)]);
+        if links_valid && !links.clone().any(|x| x.iter().any(|link| *link as usize >= article_count)) {
+            let (expected_offsets, expected_neighbors) = binary::transpose(article_count, links);
+            for article in 0..article_count {
+                if &expected_neighbors[item_range(&expected_offsets, article)] != reverse_links(article) {
+                    issues.error(
+                        Location::Article(article as u32),
+                        "Reverse links do not match the links of the other direction");
+                }
+            }
+        }
+    }
+
+    // Titles
+    let title_offsets = u64s(&sections.title_offsets);
+    let title_bytes = &bytes[sections.titles.clone()];
+    let titles_valid = check_offsets(title_offsets, title_bytes.len(), "Title offsets", &mut issues);
+    if titles_valid {
+        let mut titles: HashMap<&[u8], u32> = HashMap::with_capacity(article_count);
+        for article in 0..article_count {
+            let title = &title_bytes[item_range(title_offsets, article)];
+            if std::str::from_utf8(title).is_err() {
+                issues.error(Location::Article(article as u32), "Invalid UTF-8 in title");
+            }
+            match titles.get(title) {
+                Some(first) => issues.error(Location::Article(article as u32), format!(
+                    "Duplicate title '{}' (also article {})", String::from_utf8_lossy(title), first)),
+                None => {
+                    titles.insert(title, article as u32);
+                }
+            }
+        }
+    }
+
+    let title_order = u32s(&sections.title_order);
+    let mut listed = vec![false; article_count];
+    for (position, article) in title_order.iter().enumerate() {
+        match listed.get_mut(*article as usize) {
+            Some(true) => issues.error(Location::Dataset, format!(
+                "Article {} is listed twice in the title order", article)),
+            Some(x) => *x = true,
+            None => issues.error(Location::Dataset, format!(
+                "Article {} in the title order is out of range", article))
+        }
+        if titles_valid && position > 0 {
+            let previous = title_order[position - 1] as usize;
+            if previous < article_count && (*article as usize) < article_count
+                && title_bytes[item_range(title_offsets, previous)] >= title_bytes[item_range(title_offsets, *article as usize)] {
+                issues.error(Location::Dataset, format!(
+                    "Title order is not sorted at position {} (articles {} and {})", position, previous, article));
+            }
+        }
+    }
+
+    // Redirects
+    let redirect_count = header.redirect_count as usize;
+    let redirect_targets = u32s(&sections.redirect_targets);
+    let redirect_title_offsets = u64s(&sections.redirect_title_offsets);
+    let redirect_titles = &bytes[sections.redirect_titles.clone()];
+    if check_offsets(redirect_title_offsets, redirect_titles.len(), "Redirect title offsets", &mut issues) {
+        for redirect in 0..redirect_count {
+            let name = &redirect_titles[item_range(redirect_title_offsets, redirect)];
+            if std::str::from_utf8(name).is_err() {
+                issues.error(Location::Redirect(redirect), "Invalid UTF-8 in redirect name");
+            }
+            if redirect > 0 && &redirect_titles[item_range(redirect_title_offsets, redirect - 1)] >= name {
+                issues.error(Location::Redirect(redirect), format!(
+                    "Redirect '{}' is not sorted or is duplicated", String::from_utf8_lossy(name)));
+            }
+        }
+    }
+    for (redirect, article) in redirect_targets.iter().enumerate() {
+        if *article as usize >= article_count {
+            issues.error(Location::Redirect(redirect), format!(
+                "Redirect to article {} out of range (there are {} articles)", article, article_count));
+        }
+    }
+
+    let metadata = &bytes[sections.metadata.clone()];
+    let text = String::from_utf8_lossy(metadata);
+    if std::str::from_utf8(metadata).is_err() {
+        issues.error(Location::Dataset, "Invalid UTF-8 in metadata");
+    }
+    match Metadata::from_lines(text.lines().enumerate().map(|(i, x)| (i + 1, x)), path) {
+        Ok(metadata) => {
+            if metadata.mode != header.mode {
+                issues.error(Location::Dataset, "Metadata mode does not match the header");
+            }
+            check_metadata(&metadata, header.article_count, header.link_count, &mut issues);
+        },
+        Err(e) => issues.parse_error(e)
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        return std::env::temp_dir()
+            .join(format!("validate-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string();
+    }
+
+    fn validate_tsv_contents(name: &str, contents: &[u8]) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = Vec::new();
+        validate_tsv_rows(contents, name, &mut issues).unwrap();
+        return issues;
+    }
+
+    #[test]
+    fn valid_tsv_has_no_issues() {
+        let issues = validate_tsv_contents("valid.tsv", b"#mode\tincoming\n#articles\t3\n#links\t3\n\
+                                                          0\tAlpha\t1\t2\n1\tBeta\t\n2\tGamma\t0\n");
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_every_tsv_issue_with_line_numbers() {
+        let issues = validate_tsv_contents("invalid.tsv", b"0\tAlpha\t2\t1\t1\n1\tBeta\t1\t9\n\
+                                                            3\tAlpha\n4\tBad \xff title\tx\n");
+        let found: Vec<(Severity, Location)> = issues.iter().map(|x| (x.severity, x.location.clone())).collect();
+        assert_eq!(found, vec![
+            (Severity::Error, Location::Line(3)),
+            (Severity::Error, Location::Line(3)),
+            (Severity::Error, Location::Line(4)),
+            (Severity::Error, Location::Line(4)),
+            (Severity::Error, Location::Line(1)),
+            (Severity::Error, Location::Line(1)),
+            (Severity::Error, Location::Line(2)),
+            (Severity::Warning, Location::Line(2))
+        ]);
+        assert!(issues[0].message.contains("Expected article index 2"));
+        assert!(issues[1].message.contains("Duplicate article 'Alpha'"));
+    }
+
+    #[test]
+    fn self_links_use_the_written_article_index() {
+        // The second row is article 2 (article 1 is missing), so its link to 1 is not a self link
+        let issues = validate_tsv_contents("gap.tsv", b"0\tAlpha\t2\n2\tBeta\t1\n");
+        assert!(issues.iter().all(|x| !x.message.contains("Links to itself")), "{:?}", issues);
+
+        let issues = validate_tsv_contents("gap.tsv", b"0\tAlpha\t1\n2\tBeta\t0\t2\n");
+        let self_links: Vec<&Location> = issues
+            .iter()
+            .filter(|x| x.message.contains("Links to itself"))
+            .map(|x| &x.location)
+            .collect();
+        assert_eq!(self_links, vec![&Location::Line(2)]);
+    }
+
+    #[test]
+    fn reports_inconsistent_directions() {
+        let fixture = format!("{}/tests/fixtures/redirects.xml", env!("CARGO_MANIFEST_DIR"));
+        let (article_map, mut articles, redirects_map) =
+            parse::parse_xml_dump(&fixture, parse::ParseOptions::default()).unwrap();
+        let path = temp_path("directions.graph");
+        binary::write_binary(
+            &path, &Metadata::new(parse::ParserMode::IncomingLinks), &article_map, &mut articles, &redirects_map
+        ).unwrap();
+        assert!(validate_dataset(&path).unwrap().iter().all(|x| x.severity == Severity::Warning));
+
+        // Swap the first two reverse links, so they no longer match the links
+        let mut bytes = std::fs::read(&path).unwrap();
+        let header = Header::read(&bytes, &path).unwrap();
+        let sections = Sections::new(&header, bytes.len() as u64, &path).unwrap();
+        let start = sections.reverse_neighbors.start;
+        let (first, second) = bytes[start..start + 8].split_at_mut(4);
+        first.swap_with_slice(second);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let issues = validate_dataset(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(issues.unwrap().iter().any(|x| x.message.contains("Reverse links do not match")));
+    }
+}